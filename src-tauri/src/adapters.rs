@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[typeshare]
+/// Whether an adapter can be used
+pub enum AdapterState {
+    Available,
+    /// Still plugged in, but not answering requests, usually because it's powered off.
+    /// btleplug doesn't report power state directly, so this is how it shows up
+    Unavailable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+#[serde(tag = "type", content = "content")]
+/// Changes to the Bluetooth adapters on the system, as sent to the frontend
+pub enum AdapterEvent {
+    AdapterAdded { name: String, state: AdapterState },
+    AdapterRemoved { name: String },
+    AdapterStateChanged { name: String, state: AdapterState },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// What to do when the adapter asked for can't be used
+pub enum AdapterFallback {
+    /// Fail, so the user can pick another adapter
+    #[default]
+    Strict,
+    /// Use the first available adapter instead
+    AnyAvailable,
+}
+
+#[derive(Debug, Default)]
+/// Keeps track of the adapters on the system, reporting what changed each time it's updated
+pub struct AdapterTracker {
+    adapters: BTreeMap<String, AdapterState>,
+}
+
+impl AdapterTracker {
+    /// Replaces the known adapters with a fresh listing, returning what changed
+    pub fn update(
+        &mut self,
+        adapters: impl IntoIterator<Item = (String, AdapterState)>,
+    ) -> Vec<AdapterEvent> {
+        let adapters: BTreeMap<_, _> = adapters.into_iter().collect();
+        let mut events = Vec::new();
+
+        for name in self.adapters.keys() {
+            if !adapters.contains_key(name) {
+                events.push(AdapterEvent::AdapterRemoved { name: name.clone() });
+            }
+        }
+        for (name, state) in &adapters {
+            match self.adapters.get(name) {
+                None => events.push(AdapterEvent::AdapterAdded {
+                    name: name.clone(),
+                    state: *state,
+                }),
+                Some(previous) if previous != state => {
+                    events.push(AdapterEvent::AdapterStateChanged {
+                        name: name.clone(),
+                        state: *state,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        self.adapters = adapters;
+        events
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.adapters.keys().cloned().collect()
+    }
+
+    pub fn state(&self, name: &str) -> Option<AdapterState> {
+        self.adapters.get(name).copied()
+    }
+
+    /// Picks the adapter to use when `requested` is asked for, or `None` if there isn't one
+    pub fn resolve(&self, requested: &str, fallback: AdapterFallback) -> Option<&str> {
+        if self.state(requested) == Some(AdapterState::Available) {
+            return Some(self.adapters.get_key_value(requested)?.0);
+        }
+
+        match fallback {
+            AdapterFallback::Strict => None,
+            AdapterFallback::AnyAvailable => self.first_available(),
+        }
+    }
+
+    /// The first available adapter by name, for when no particular one was asked for
+    pub fn first_available(&self) -> Option<&str> {
+        self.adapters
+            .iter()
+            .find(|(_, state)| **state == AdapterState::Available)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapters(list: &[(&str, AdapterState)]) -> Vec<(String, AdapterState)> {
+        list.iter()
+            .map(|(name, state)| (name.to_string(), *state))
+            .collect()
+    }
+
+    #[test]
+    fn reports_added_removed_and_changed_adapters() {
+        let mut tracker = AdapterTracker::default();
+        tracker.update(adapters(&[
+            ("hci0", AdapterState::Available),
+            ("hci1", AdapterState::Available),
+        ]));
+
+        let events = tracker.update(adapters(&[
+            ("hci0", AdapterState::Unavailable),
+            ("hci2", AdapterState::Available),
+        ]));
+
+        assert_eq!(
+            events,
+            vec![
+                AdapterEvent::AdapterRemoved {
+                    name: String::from("hci1")
+                },
+                AdapterEvent::AdapterStateChanged {
+                    name: String::from("hci0"),
+                    state: AdapterState::Unavailable
+                },
+                AdapterEvent::AdapterAdded {
+                    name: String::from("hci2"),
+                    state: AdapterState::Available
+                },
+            ]
+        );
+        assert_eq!(tracker.names(), vec!["hci0", "hci2"]);
+    }
+
+    #[test]
+    fn only_falls_back_when_asked_to() {
+        let mut tracker = AdapterTracker::default();
+        tracker.update(adapters(&[
+            ("hci0", AdapterState::Unavailable),
+            ("hci1", AdapterState::Available),
+        ]));
+
+        assert_eq!(
+            tracker.resolve("hci1", AdapterFallback::Strict),
+            Some("hci1")
+        );
+        assert_eq!(tracker.resolve("hci0", AdapterFallback::Strict), None);
+        assert_eq!(tracker.resolve("hci9", AdapterFallback::Strict), None);
+        assert_eq!(
+            tracker.resolve("hci0", AdapterFallback::AnyAvailable),
+            Some("hci1")
+        );
+    }
+}
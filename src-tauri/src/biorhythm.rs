@@ -0,0 +1,175 @@
+//! Biorhythm programs and the fragments the device stores them in.
+//!
+//! Nothing documents the layout of the fragments. It's taken to be a count byte followed by one
+//! 5 byte entry per step, spread across the four fragment parameters, which hasn't been confirmed
+//! against a real device. Writing programs is only enabled with the `biorhythm-writes` feature.
+
+use crate::{proto::OperatingMode, temperature::Temperature, InterfaceError};
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+
+/// How many bytes each of the four fragment parameters holds
+pub const FRAGMENT_LEN: usize = 16;
+pub const FRAGMENT_COUNT: usize = 4;
+/// Each step is stored as mode, temperature, fan step, hours, minutes
+const STEP_LEN: usize = 5;
+/// The first byte of the program holds the number of steps
+pub const MAX_STEPS: usize = (FRAGMENT_LEN * FRAGMENT_COUNT - 1) / STEP_LEN;
+
+pub type Fragments = [[u8; FRAGMENT_LEN]; FRAGMENT_COUNT];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// A single step of a biorhythm program
+pub struct BiorhythmStep {
+    pub mode: OperatingMode,
+    pub temperature: Temperature,
+    /// Represented as a number between 0-19
+    pub fan_step: u8,
+    /// How long the step runs for
+    pub hours: u8,
+    pub minutes: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// A named, ordered list of steps stored in one of the device's three biorhythm slots
+pub struct BiorhythmProgram {
+    /// Cannot contain a String longer than 15 bytes.
+    pub name: String,
+    pub steps: Vec<BiorhythmStep>,
+}
+
+impl BiorhythmStep {
+    fn write_to(&self, out: &mut [u8]) -> Result<(), InterfaceError> {
+        if self.fan_step > 19 || self.minutes > 59 {
+            return Err(InterfaceError::InvalidParameter);
+        }
+
+        out.copy_from_slice(&[
+            self.mode as u8,
+            self.temperature.half_degrees(),
+            self.fan_step,
+            self.hours,
+            self.minutes,
+        ]);
+        Ok(())
+    }
+
+    fn read_from(bytes: &[u8]) -> Result<Self, InterfaceError> {
+        let [mode, temperature, fan_step, hours, minutes] = bytes else {
+            return Err(InterfaceError::NotEnoughData);
+        };
+        let mode = OperatingMode::from_u8(*mode).ok_or(InterfaceError::InvalidParameter)?;
+        if *fan_step > 19 || *minutes > 59 {
+            return Err(InterfaceError::InvalidParameter);
+        }
+
+        Ok(Self {
+            mode,
+            temperature: Temperature::from_half_degrees(*temperature),
+            fan_step: *fan_step,
+            hours: *hours,
+            minutes: *minutes,
+        })
+    }
+}
+
+impl BiorhythmProgram {
+    /// Splits the steps into the four fragments the device stores them in.
+    /// The name is stored separately, in the matching `BiorhythmName` parameter
+    pub fn fragments(&self) -> Result<Fragments, InterfaceError> {
+        if self.steps.len() > MAX_STEPS {
+            return Err(InterfaceError::InvalidParameter);
+        }
+
+        let mut program = [0u8; FRAGMENT_LEN * FRAGMENT_COUNT];
+        program[0] = self.steps.len() as u8;
+        for (step, out) in self
+            .steps
+            .iter()
+            .zip(program[1..].chunks_exact_mut(STEP_LEN))
+        {
+            step.write_to(out)?;
+        }
+
+        let mut fragments = [[0u8; FRAGMENT_LEN]; FRAGMENT_COUNT];
+        for (fragment, chunk) in fragments.iter_mut().zip(program.chunks_exact(FRAGMENT_LEN)) {
+            fragment.copy_from_slice(chunk);
+        }
+        Ok(fragments)
+    }
+
+    /// Reassembles a program from its name and the four fragments read from the device
+    pub fn from_fragments(name: String, fragments: &Fragments) -> Result<Self, InterfaceError> {
+        let program = fragments.concat();
+        let count = program[0] as usize;
+        if count > MAX_STEPS {
+            return Err(InterfaceError::InvalidParameter);
+        }
+
+        let steps = program[1..]
+            .chunks_exact(STEP_LEN)
+            .take(count)
+            .map(BiorhythmStep::read_from)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { name, steps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(minutes: u8) -> BiorhythmStep {
+        BiorhythmStep {
+            mode: OperatingMode::Cool,
+            temperature: Temperature::from_half_degrees(40),
+            fan_step: 10,
+            hours: minutes / 60,
+            minutes: minutes % 60,
+        }
+    }
+
+    #[test]
+    fn steps_are_packed_across_fragments() {
+        let program = BiorhythmProgram {
+            name: String::from("Night"),
+            steps: vec![step(90); 4],
+        };
+
+        let fragments = program.fragments().unwrap();
+
+        assert_eq!(fragments[0][..6], [4, 4, 40, 10, 1, 30]);
+        // Three steps fill the first fragment, so the fourth starts the second
+        assert_eq!(fragments[0][15], 30);
+        assert_eq!(fragments[1][..5], [4, 40, 10, 1, 30]);
+        assert_eq!(
+            BiorhythmProgram::from_fragments(program.name.clone(), &fragments).unwrap(),
+            program
+        );
+    }
+
+    #[test]
+    fn steps_serialize_hours_and_minutes() {
+        let step = serde_json::to_value(step(90)).unwrap();
+
+        assert_eq!(step["hours"], 1);
+        assert_eq!(step["minutes"], 30);
+    }
+
+    #[test]
+    fn too_many_steps_are_rejected() {
+        let program = BiorhythmProgram {
+            name: String::from("Long"),
+            steps: vec![step(10); MAX_STEPS + 1],
+        };
+
+        assert!(matches!(
+            program.fragments(),
+            Err(InterfaceError::InvalidParameter)
+        ));
+    }
+}
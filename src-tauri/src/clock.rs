@@ -0,0 +1,223 @@
+use crate::{device::BedJet, supervisor::ConnectionState, transport::BedJetTransport, Command};
+use chrono::{FixedOffset, Local, NaiveTime, Timelike};
+use std::time::Duration;
+use tokio::{
+    sync::watch,
+    time::{self, Instant},
+};
+
+#[derive(Debug, Clone, Copy)]
+/// Decides when a device's clock needs setting: after it connects, once the interval has passed,
+/// and when the local UTC offset changes, like at the start or end of daylight saving time
+pub struct ClockSync {
+    interval: Duration,
+    last_sent: Option<(Instant, FixedOffset)>,
+}
+
+impl ClockSync {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+    /// How often the UTC offset is checked
+    pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_sent: None,
+        }
+    }
+
+    pub fn is_due(&self, now: Instant, offset: FixedOffset) -> bool {
+        match self.last_sent {
+            Some((sent_at, sent_offset)) => {
+                now.duration_since(sent_at) >= self.interval || offset != sent_offset
+            }
+            None => true,
+        }
+    }
+
+    /// Records that the clock was set
+    pub fn sent(&mut self, now: Instant, offset: FixedOffset) {
+        self.last_sent = Some((now, offset));
+    }
+
+    /// Makes the clock due to be set again, like after the device reconnects
+    pub fn reset(&mut self) {
+        self.last_sent = None;
+    }
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_INTERVAL)
+    }
+}
+
+/// Keeps a device's clock set to local time while `enabled` is true, until either watch closes.
+/// `state` is the device's connection state, from its [`Supervisor`](crate::supervisor::Supervisor)
+pub async fn keep_clock_in_sync<T: BedJetTransport>(
+    bedjet: &BedJet<T>,
+    mut state: watch::Receiver<ConnectionState>,
+    mut enabled: watch::Receiver<bool>,
+    mut sync: ClockSync,
+) {
+    let mut check = time::interval(ClockSync::CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            changed = state.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                sync.reset();
+            }
+            changed = enabled.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                sync.reset();
+            }
+            _ = check.tick() => {}
+        }
+
+        if !*enabled.borrow() || *state.borrow() != ConnectionState::Connected {
+            continue;
+        }
+        if !sync.is_due(Instant::now(), *Local::now().offset()) {
+            continue;
+        }
+        // The device only takes hours and minutes, so the clock is set as the minute turns over
+        // rather than leaving it up to a minute behind
+        time::sleep(until_next_minute(Local::now().time())).await;
+        let local = Local::now();
+        if bedjet
+            .send_command(Command::set_clock(local.time()))
+            .await
+            .is_ok()
+        {
+            sync.sent(Instant::now(), *local.offset());
+        }
+    }
+}
+
+/// How long until the clock next reaches a whole minute, or zero if it's on one
+fn until_next_minute(time: NaiveTime) -> Duration {
+    let into_minute =
+        Duration::from_secs(time.second() as u64) + Duration::from_nanos(time.nanosecond() as u64);
+    if into_minute.is_zero() {
+        return Duration::ZERO;
+    }
+    // A leap second can run the nanoseconds past a full second
+    Duration::from_secs(60).saturating_sub(into_minute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{proto::CommandClass, transport::MemoryTransport, Encode};
+    use std::sync::Arc;
+
+    #[test]
+    fn encodes_the_time_of_day() {
+        let midnight = Command::set_clock(NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(
+            midnight.encode().unwrap(),
+            vec![CommandClass::SetClock as u8, 0, 0]
+        );
+
+        // Seconds are dropped rather than rounded, so this doesn't roll over to 00:00
+        let last_minute = Command::set_clock(NaiveTime::from_hms_opt(23, 59, 59).unwrap());
+        assert_eq!(
+            last_minute.encode().unwrap(),
+            vec![CommandClass::SetClock as u8, 23, 59]
+        );
+
+        assert!(Command::SetClock {
+            hours: 24,
+            minutes: 0
+        }
+        .encode()
+        .is_err());
+    }
+
+    #[test]
+    fn is_due_after_the_interval_or_an_offset_change() {
+        let mut sync = ClockSync::new(Duration::from_secs(3600));
+        let now = Instant::now();
+        let winter = FixedOffset::east_opt(0).unwrap();
+        let summer = FixedOffset::east_opt(3600).unwrap();
+        assert!(sync.is_due(now, winter));
+
+        sync.sent(now, winter);
+        assert!(!sync.is_due(now + Duration::from_secs(60), winter));
+        assert!(sync.is_due(now + Duration::from_secs(60), summer));
+        assert!(sync.is_due(now + Duration::from_secs(3600), winter));
+
+        sync.reset();
+        assert!(sync.is_due(now, winter));
+    }
+
+    #[test]
+    fn waits_for_the_next_whole_minute() {
+        let time = |h, m, s, milli| NaiveTime::from_hms_milli_opt(h, m, s, milli).unwrap();
+
+        assert_eq!(until_next_minute(time(7, 30, 0, 0)), Duration::ZERO);
+        assert_eq!(
+            until_next_minute(time(7, 30, 45, 500)),
+            Duration::from_millis(14_500)
+        );
+        assert_eq!(
+            until_next_minute(time(23, 59, 59, 999)),
+            Duration::from_millis(1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sets_the_clock_on_connect_and_every_interval() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let (state, state_receiver) = watch::channel(ConnectionState::Connecting);
+        let (enabled, enabled_receiver) = watch::channel(true);
+        let clock_writes = || {
+            transport
+                .writes()
+                .iter()
+                .filter(|(_, data)| data[0] == CommandClass::SetClock as u8)
+                .count()
+        };
+
+        let task = tokio::spawn({
+            let bedjet = bedjet.clone();
+            async move {
+                keep_clock_in_sync(
+                    &bedjet,
+                    state_receiver,
+                    enabled_receiver,
+                    ClockSync::new(Duration::from_secs(3600)),
+                )
+                .await
+            }
+        });
+        time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(clock_writes(), 0);
+
+        // Each time the clock is due, it's set within a minute, when the minute turns over
+        state.send_replace(ConnectionState::Connected);
+        time::sleep(Duration::from_secs(61)).await;
+        assert_eq!(clock_writes(), 1);
+
+        time::sleep(Duration::from_secs(30 * 60)).await;
+        assert_eq!(clock_writes(), 1);
+        time::sleep(Duration::from_secs(33 * 60)).await;
+        assert_eq!(clock_writes(), 2);
+
+        state.send_replace(ConnectionState::Reconnecting { attempt: 1 });
+        state.send_replace(ConnectionState::Connected);
+        time::sleep(Duration::from_secs(61)).await;
+        assert_eq!(clock_writes(), 3);
+
+        enabled.send_replace(false);
+        time::sleep(Duration::from_secs(2 * 3600)).await;
+        assert_eq!(clock_writes(), 3);
+
+        task.abort();
+    }
+}
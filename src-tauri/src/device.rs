@@ -1,15 +1,27 @@
-use crate::{proto::DeviceStatus, Command, Decode, Encode, InterfaceError};
-use btleplug::{
-    api::{Characteristic, Peripheral as _, WriteType},
-    platform::Peripheral,
+use crate::{
+    biorhythm::{BiorhythmProgram, Fragments},
+    firmware::{FirmwareInfo, FirmwareUpdateEvent, FirmwareUpdateOutcome},
+    proto::{ButtonCode, DeviceSetting, DeviceStatus, ParameterCode, UpdateStatus},
+    read_name,
+    temperature::Temperature,
+    transport::{BedJetTransport, NotificationStream, PeripheralTransport},
+    validate_name, Command, Decode, Encode, InterfaceError, Parameter, ParameterValue,
+    SetParamKind, TempParam,
 };
+use btleplug::platform::Peripheral;
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
     io::{Cursor, Read},
+    sync::Mutex,
+    time::Duration,
 };
 use thiserror::Error;
-use tokio::sync::watch;
+use tokio::{
+    sync::{watch, Mutex as AsyncMutex},
+    time,
+};
+use typeshare::typeshare;
 use uuid::Uuid;
 
 #[derive(Error, Debug)]
@@ -18,19 +30,41 @@ pub enum DeviceError {
     InterfaceError(#[from] InterfaceError),
     #[error("Bluetooth Error {0}")]
     BluetoothError(#[from] btleplug::Error),
+    #[error("Device does not have characteristic {0}")]
+    MissingCharacteristic(Uuid),
+    #[error("Timed out waiting for the device to respond")]
+    Timeout,
+    #[error("Wi-Fi connection test failed: {0:?}")]
+    ConnectionTestFailed(UpdateStatus),
+    #[error("Firmware update failed: {0:?}")]
+    FirmwareUpdateFailed(UpdateStatus),
+    #[error("Device reports its name as {actual:?} instead of {expected:?}")]
+    NameNotApplied { expected: String, actual: String },
+    #[error("Temperature must be between {min} and {max} in the current mode")]
+    OutOfRange { min: Temperature, max: Temperature },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// What [`BedJet::send_command`] does with a temperature outside the range the current mode allows
+pub enum TemperaturePolicy {
+    /// Fail with [`DeviceError::OutOfRange`]
+    #[default]
+    Reject,
+    /// Send the closest temperature that is allowed instead
+    Clamp,
 }
 
 #[derive(Debug)]
 /// The primary interface for interacting with the device.
-pub struct BedJet {
-    peripheral: Peripheral,
-    device_status: Characteristic,
-    friendly_name: Characteristic,
-    wifi_ssid: Characteristic,
-    wifi_password: Characteristic,
-    command: Characteristic,
-    extended_data: Characteristic,
+pub struct BedJet<T: BedJetTransport = PeripheralTransport> {
+    transport: T,
     device_status_send: watch::Sender<Option<DeviceStatus>>,
+    parameter_timeout: Mutex<Duration>,
+    temperature_policy: Mutex<TemperaturePolicy>,
+    firmware: Mutex<Option<FirmwareInfo>>,
+    /// Held for the whole of a parameter request, so each one knows which reply is its own
+    parameter_request: AsyncMutex<()>,
 }
 
 impl BedJet {
@@ -42,47 +76,110 @@ impl BedJet {
     pub const COMMANDS_UUID: Uuid = Uuid::from_u128(649413073577720503353409796728180);
     pub const EXTENDED_DATA_UUID: Uuid = Uuid::from_u128(649492301740234767691003340678516);
 
-    pub fn from_peripheral(peripheral: Peripheral) -> Option<Self> {
-        let mut map: HashMap<Uuid, Characteristic> = peripheral
-            .characteristics()
-            .into_iter()
-            .map(|c| (c.uuid, c))
-            .collect();
+    /// Wraps a peripheral whose services have been discovered, failing if it's missing any of
+    /// the characteristics a BedJet has
+    pub fn from_peripheral(peripheral: Peripheral) -> Result<Self, DeviceError> {
+        Ok(Self::new(Self::peripheral_transport(peripheral)?))
+    }
+
+    /// The transport for a peripheral whose services have been discovered, failing if it's
+    /// missing any of the characteristics a BedJet has
+    pub fn peripheral_transport(
+        peripheral: Peripheral,
+    ) -> Result<PeripheralTransport, DeviceError> {
+        let transport = PeripheralTransport::new(peripheral);
+
+        let missing = [
+            Self::DEVICE_STATUS_UUID,
+            Self::FRIENDLY_NAME_UUID,
+            Self::WIFI_SSID_UUID,
+            Self::WIFI_PASSWORD_UUID,
+            Self::COMMANDS_UUID,
+            Self::EXTENDED_DATA_UUID,
+        ]
+        .into_iter()
+        .find(|uuid| !transport.has_characteristic(uuid));
+
+        if let Some(uuid) = missing {
+            return Err(DeviceError::MissingCharacteristic(uuid));
+        }
+
+        Ok(transport)
+    }
+
+    pub fn peripheral(&self) -> &Peripheral {
+        self.transport.peripheral()
+    }
+}
+
+impl<T: BedJetTransport> BedJet<T> {
+    pub const DEFAULT_PARAMETER_TIMEOUT: Duration = Duration::from_secs(2);
+    pub const CONNECTION_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+    pub const FIRMWARE_UPDATE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+    pub const MAX_SSID_LEN: usize = 32;
+    pub const MAX_PASSWORD_LEN: usize = 63;
+    const PARAMETER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+    /// How long the device gets to answer a parameter request before a reply that's the same
+    /// as the one before it is believed, since the value may just not have changed
+    const UNCHANGED_PARAMETER_DELAY: Duration = Duration::from_millis(500);
 
+    pub fn new(transport: T) -> Self {
         let (device_status_send, _) = watch::channel(None);
 
-        Some(Self {
-            peripheral,
-            device_status: map.remove(&Self::DEVICE_STATUS_UUID)?,
-            friendly_name: map.remove(&Self::FRIENDLY_NAME_UUID)?,
-            wifi_ssid: map.remove(&Self::WIFI_PASSWORD_UUID)?,
-            wifi_password: map.remove(&Self::WIFI_PASSWORD_UUID)?,
-            command: map.remove(&Self::COMMANDS_UUID)?,
-            extended_data: map.remove(&Self::EXTENDED_DATA_UUID)?,
+        Self {
+            transport,
             device_status_send,
-        })
+            parameter_timeout: Mutex::new(Self::DEFAULT_PARAMETER_TIMEOUT),
+            temperature_policy: Mutex::default(),
+            firmware: Mutex::new(None),
+            parameter_request: AsyncMutex::new(()),
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Subscribes to the device status, and processes notifications until the stream ends
+    pub async fn handle_notifications(&self) -> Result<(), DeviceError> {
+        let stream = self.listen().await?;
+        self.process_notifications(stream).await;
+
+        Ok(())
     }
 
-    async fn handle_notifications(&self) -> Result<(), DeviceError> {
-        let mut stream = self.peripheral.notifications().await?;
+    /// Subscribes to the device status, returning the notifications to hand to
+    /// [`BedJet::process_notifications`]
+    pub async fn listen(&self) -> Result<NotificationStream, DeviceError> {
+        let stream = self.transport.notifications().await?;
+        self.listen_status().await?;
+
+        Ok(stream)
+    }
 
+    /// Processes notifications until the stream ends
+    pub async fn process_notifications(&self, mut stream: NotificationStream) {
         while let Some(msg) = stream.next().await {
             let _ = match msg.uuid {
                 BedJet::DEVICE_STATUS_UUID => self.handle_device_status(msg.value).await,
                 _ => Ok(()),
             };
         }
-
-        Ok(())
     }
 
-    async fn listen_status(&self) -> Result<(), btleplug::Error> {
-        self.peripheral.subscribe(&self.device_status).await
+    async fn listen_status(&self) -> Result<(), DeviceError> {
+        self.transport.subscribe(BedJet::DEVICE_STATUS_UUID).await
     }
 
-    async fn unlisten_status(&self) -> Result<(), btleplug::Error> {
-        self.peripheral.unsubscribe(&self.device_status).await
+    pub async fn unlisten_status(&self) -> Result<(), DeviceError> {
+        self.transport.unsubscribe(BedJet::DEVICE_STATUS_UUID).await
+    }
+    /// Every status notification, for watching the device change over time.
+    /// Only updated while [`BedJet::handle_notifications`] is running
+    pub fn subscribe_status(&self) -> watch::Receiver<Option<DeviceStatus>> {
+        self.device_status_send.subscribe()
     }
+
     pub async fn get_status(&self) -> Result<DeviceStatus, watch::error::RecvError> {
         let mut recv = self.device_status_send.subscribe();
 
@@ -105,7 +202,7 @@ impl BedJet {
         // If the entire packet isn't contained in the message we received
         if !has_enough_bytes {
             // grab the rest of it
-            let rest: Vec<u8> = self.peripheral.read(&self.device_status).await?;
+            let rest: Vec<u8> = self.transport.read(BedJet::DEVICE_STATUS_UUID).await?;
             // and decode it
             status = DeviceStatus::read_from(cursor.chain(Cursor::new(rest)))?;
         } else {
@@ -117,17 +214,914 @@ impl BedJet {
         Ok(())
     }
 
-    pub async fn get_friendly_name(&self) -> String {
-        let data = self.peripheral.read(&self.friendly_name).await.unwrap();
+    pub async fn get_friendly_name(&self) -> Result<String, DeviceError> {
+        let data = self.transport.read(BedJet::FRIENDLY_NAME_UUID).await?;
+        Ok(read_name(data)?)
+    }
+
+    /// Renames the device, and reads the name back to make sure it was applied
+    pub async fn rename(&self, name: &str) -> Result<(), DeviceError> {
+        validate_name(name)?;
+        self.send_command(Command::SetParam(SetParamKind::DeviceName(name.to_owned())))
+            .await?;
+
+        // Give the device a moment to apply it before giving up
+        let deadline = time::Instant::now() + self.parameter_timeout();
+        loop {
+            let actual = self.get_friendly_name().await?;
+            if actual == name {
+                return Ok(());
+            }
+            if time::Instant::now() >= deadline {
+                return Err(DeviceError::NameNotApplied {
+                    expected: name.to_owned(),
+                    actual,
+                });
+            }
+            time::sleep(Self::PARAMETER_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Turns one of the device's settings on or off
+    pub async fn set_setting(
+        &self,
+        setting: DeviceSetting,
+        enabled: bool,
+    ) -> Result<(), DeviceError> {
+        self.send_command(Command::Button(setting.button(enabled)))
+            .await
+    }
+
+    /// Sets what happens to temperatures the current mode doesn't allow
+    pub fn set_temperature_policy(&self, policy: TemperaturePolicy) {
+        *self.temperature_policy.lock().unwrap() = policy;
+    }
 
-        String::from_utf8(data).unwrap()
+    /// Sets how long [`BedJet::get_parameter`] waits for the device to reply
+    pub fn set_parameter_timeout(&self, timeout: Duration) {
+        *self.parameter_timeout.lock().unwrap() = timeout;
     }
+
+    fn parameter_timeout(&self) -> Duration {
+        *self.parameter_timeout.lock().unwrap()
+    }
+
+    /// Asks the device for a parameter, and decodes the reply from the extended data characteristic
+    pub async fn get_parameter(&self, code: ParameterCode) -> Result<ParameterValue, DeviceError> {
+        time::timeout(self.parameter_timeout(), self.read_parameter(code))
+            .await
+            .map_err(|_| DeviceError::Timeout)?
+    }
+
+    async fn read_parameter(&self, code: ParameterCode) -> Result<ParameterValue, DeviceError> {
+        let _request = self.parameter_request.lock().await;
+        // The characteristic holds the reply to an earlier request until the device gets around
+        // to ours, and that may even be for the same parameter
+        let previous = self.transport.read(BedJet::EXTENDED_DATA_UUID).await?;
+        self.send_command(Command::GetParam(code)).await?;
+        let sent = time::Instant::now();
+
+        loop {
+            let reply = self.transport.read(BedJet::EXTENDED_DATA_UUID).await?;
+            let fresh = reply != previous || sent.elapsed() >= Self::UNCHANGED_PARAMETER_DELAY;
+            if let Ok(param) = Parameter::read_from(reply.as_slice()) {
+                if fresh && param.code == code {
+                    return Ok(param.value);
+                }
+            }
+            time::sleep(Self::PARAMETER_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Reads the firmware versions from the device, and remembers them so commands
+    /// the firmware may not handle are logged by [`BedJet::send_command`]
+    pub async fn discover_firmware(&self) -> Result<FirmwareInfo, DeviceError> {
+        let firmware = FirmwareInfo::try_from(
+            self.get_parameter(ParameterCode::FirmwareVersionCodes)
+                .await?,
+        )?;
+        *self.firmware.lock().unwrap() = Some(firmware);
+        Ok(firmware)
+    }
+
+    /// The firmware found by [`BedJet::discover_firmware`]
+    pub fn firmware(&self) -> Option<FirmwareInfo> {
+        *self.firmware.lock().unwrap()
+    }
+
+    /// Sets the Wi-Fi network the device connects to, and checks that it can.
+    ///
+    /// Resolves once the device reports the outcome of the connection test,
+    /// so [`BedJet::handle_notifications`] needs to be running.
+    pub async fn provision_wifi(&self, ssid: &str, password: &str) -> Result<(), DeviceError> {
+        if ssid.is_empty() || ssid.len() > Self::MAX_SSID_LEN {
+            return Err(InterfaceError::InvalidParameter.into());
+        }
+        if password.len() > Self::MAX_PASSWORD_LEN {
+            return Err(InterfaceError::InvalidParameter.into());
+        }
+
+        // Ignore whatever the status was before we started
+        let mut status = self.subscribe_status();
+        status.borrow_and_update();
+
+        self.transport
+            .write(BedJet::WIFI_SSID_UUID, ssid.as_bytes())
+            .await?;
+        self.transport
+            .write(BedJet::WIFI_PASSWORD_UUID, password.as_bytes())
+            .await?;
+        self.send_command(Command::Button(ButtonCode::StartConnectionTest))
+            .await?;
+
+        let outcome = time::timeout(Self::CONNECTION_TEST_TIMEOUT, wait_for_outcome(&mut status))
+            .await
+            .map_err(|_| DeviceError::Timeout)??;
+
+        match outcome {
+            UpdateStatus::ConnectionTestOK => Ok(()),
+            failure => Err(DeviceError::ConnectionTestFailed(failure)),
+        }
+    }
+
+    /// Has the device download and install the latest firmware over Wi-Fi.
+    ///
+    /// Each change in the device's update status is reported to `on_event`. The device restarts
+    /// to finish an update, in which case this waits for it to be reconnected to and send its
+    /// status again, and then reads back the new firmware. Reconnecting is left to whatever
+    /// keeps the connection up, like a [`Supervisor`](crate::supervisor::Supervisor).
+    pub async fn update_firmware<F>(
+        &self,
+        on_event: F,
+    ) -> Result<FirmwareUpdateOutcome, DeviceError>
+    where
+        F: FnMut(FirmwareUpdateEvent) + Send,
+    {
+        time::timeout(
+            Self::FIRMWARE_UPDATE_TIMEOUT,
+            self.run_firmware_update(on_event),
+        )
+        .await
+        .map_err(|_| DeviceError::Timeout)?
+    }
+
+    async fn run_firmware_update<F>(
+        &self,
+        mut on_event: F,
+    ) -> Result<FirmwareUpdateOutcome, DeviceError>
+    where
+        F: FnMut(FirmwareUpdateEvent) + Send,
+    {
+        // There's no point asking for an update if the device can't get online
+        let ssid = self.transport.read(BedJet::WIFI_SSID_UUID).await?;
+        if ssid.iter().all(|b| *b == 0) {
+            return Err(DeviceError::FirmwareUpdateFailed(
+                UpdateStatus::NoWiFiConfig,
+            ));
+        }
+
+        // Ignore whatever the status was before we started
+        let mut status = self.subscribe_status();
+        status.borrow_and_update();
+        self.send_command(Command::Button(ButtonCode::StartFirmwareUpdate))
+            .await?;
+
+        let mut last = None;
+        loop {
+            // The sender lives as long as the BedJet, so this only fails if the device goes away
+            status.changed().await.map_err(|_| DeviceError::Timeout)?;
+            let Some(update_status) = status.borrow().map(|status| status.update_status) else {
+                continue;
+            };
+            if last == Some(update_status) {
+                continue;
+            }
+            last = Some(update_status);
+            on_event(FirmwareUpdateEvent::Progress(update_status));
+
+            match update_status {
+                UpdateStatus::NoUpdateNeeded => return Ok(FirmwareUpdateOutcome::UpToDate),
+                UpdateStatus::RestartingBedJet => break,
+                failure if failure.is_failure() => {
+                    return Err(DeviceError::FirmwareUpdateFailed(failure))
+                }
+                _ => {}
+            }
+        }
+
+        on_event(FirmwareUpdateEvent::Reconnecting);
+        // The device keeps reporting the restart until it goes away, so the first status that
+        // moved on comes from the restarted device, once it has been reconnected to
+        loop {
+            status.changed().await.map_err(|_| DeviceError::Timeout)?;
+            let update_status = status.borrow().map(|status| status.update_status);
+            if update_status.is_some_and(|status| status != UpdateStatus::RestartingBedJet) {
+                break;
+            }
+        }
+
+        Ok(FirmwareUpdateOutcome::Updated(
+            self.discover_firmware().await?,
+        ))
+    }
+
+    /// Names memory slot 1 to 3
+    pub async fn set_memory_name(&self, slot: u8, name: String) -> Result<(), DeviceError> {
+        self.send_command(Command::SetParam(SetParamKind::MemoryName { slot, name }))
+            .await
+    }
+
+    /// Reads the name of memory slot 1 to 3
+    pub async fn read_memory_name(&self, slot: u8) -> Result<String, DeviceError> {
+        let code = ParameterCode::memory_name(slot).ok_or(InterfaceError::InvalidParameter)?;
+        let ParameterValue::Name(name) = self.get_parameter(code).await? else {
+            return Err(InterfaceError::InvalidParameter.into());
+        };
+        Ok(name)
+    }
+
+    /// Reads the names of all three memory slots
+    pub async fn read_memory_names(&self) -> Result<Vec<String>, DeviceError> {
+        let mut names = Vec::with_capacity(3);
+        for slot in 1..=3 {
+            names.push(self.read_memory_name(slot).await?);
+        }
+        Ok(names)
+    }
+
+    /// Stores a program in biorhythm slot 1 to 3. The layout of the fragments is unconfirmed, so
+    /// this is behind the `biorhythm-writes` feature
+    #[cfg(any(test, feature = "biorhythm-writes"))]
+    pub async fn write_biorhythm(
+        &self,
+        slot: u8,
+        program: &BiorhythmProgram,
+    ) -> Result<(), DeviceError> {
+        let fragments = program.fragments()?;
+
+        self.send_command(Command::SetParam(SetParamKind::BiorhythmName {
+            slot,
+            name: program.name.clone(),
+        }))
+        .await?;
+
+        for (fragment, data) in (1..).zip(fragments) {
+            self.send_command(Command::SetParam(SetParamKind::BiorhythmFragment {
+                slot,
+                fragment,
+                data,
+            }))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads back the program stored in biorhythm slot 1 to 3
+    pub async fn read_biorhythm(&self, slot: u8) -> Result<BiorhythmProgram, DeviceError> {
+        let code = ParameterCode::biorhythm_name(slot).ok_or(InterfaceError::InvalidParameter)?;
+        let ParameterValue::Name(name) = self.get_parameter(code).await? else {
+            return Err(InterfaceError::InvalidParameter.into());
+        };
+
+        let mut fragments: Fragments = Default::default();
+        for (fragment, out) in (1..).zip(fragments.iter_mut()) {
+            let code = ParameterCode::biorhythm_fragment(slot, fragment)
+                .ok_or(InterfaceError::InvalidParameter)?;
+            let ParameterValue::BiorhythmFragment(data) = self.get_parameter(code).await? else {
+                return Err(InterfaceError::InvalidParameter.into());
+            };
+            *out = data;
+        }
+
+        Ok(BiorhythmProgram::from_fragments(name, &fragments)?)
+    }
+
+    /// Reads back the programs stored in all three biorhythm slots
+    pub async fn read_biorhythms(&self) -> Result<Vec<BiorhythmProgram>, DeviceError> {
+        let mut programs = Vec::with_capacity(3);
+        for slot in 1..=3 {
+            programs.push(self.read_biorhythm(slot).await?);
+        }
+        Ok(programs)
+    }
+
+    /// Sends a command to the device.
+    ///
+    /// Once the firmware is known, commands it may not support are logged, since the device
+    /// could silently ignore them.
     pub async fn send_command(&self, command: Command) -> Result<(), DeviceError> {
+        if let Some(firmware) = self.firmware() {
+            if let Err(required) = firmware.check(&command) {
+                log::warn!(
+                    "{command} may need firmware {required} or newer, but the device has {}",
+                    firmware.main_board
+                );
+            }
+        }
+
+        let command = self.check_temperature(command)?;
         let data = command.encode()?;
-        self.peripheral
-            .write(&self.command, &data, WriteType::WithoutResponse)
-            .await?;
+        self.transport.write(BedJet::COMMANDS_UUID, &data).await?;
 
         Ok(())
     }
+
+    /// Checks a temperature command against the range in the latest status, applying the
+    /// [`TemperaturePolicy`]. Commands go out unchanged until a status has been received
+    fn check_temperature(&self, command: Command) -> Result<Command, DeviceError> {
+        let Command::SetTemp(param) = &command else {
+            return Ok(command);
+        };
+        let Some(status) = *self.device_status_send.borrow() else {
+            return Ok(command);
+        };
+
+        let temp = param.temperature()?;
+        let (min, max) = (status.min_target_temp, status.max_target_temp);
+        // Don't trust a range the device hasn't filled in, which reads as all zeroes
+        if min >= max || (min..=max).contains(&temp) {
+            return Ok(command);
+        }
+
+        match *self.temperature_policy.lock().unwrap() {
+            TemperaturePolicy::Reject => Err(DeviceError::OutOfRange { min, max }),
+            TemperaturePolicy::Clamp => {
+                Ok(Command::SetTemp(TempParam::Celsius(temp.clamp(min, max))))
+            }
+        }
+    }
+}
+
+/// Waits for the device to start a connection test or firmware update, and then for it to finish.
+/// Returns the status it finished with, or the first failure, which can come without any
+/// progress being reported first
+async fn wait_for_outcome(
+    status: &mut watch::Receiver<Option<DeviceStatus>>,
+) -> Result<UpdateStatus, DeviceError> {
+    let mut started = false;
+    loop {
+        // The sender lives as long as the BedJet, so this only fails if the device goes away
+        status.changed().await.map_err(|_| DeviceError::Timeout)?;
+        let Some(update_status) = status.borrow().map(|status| status.update_status) else {
+            continue;
+        };
+
+        if update_status.is_failure() {
+            return Ok(update_status);
+        } else if update_status.is_in_progress() {
+            started = true;
+        } else if started && update_status != UpdateStatus::Idle {
+            return Ok(update_status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::SimulatedBedJet;
+    use crate::{
+        biorhythm::BiorhythmStep,
+        proto::{CommandClass, OperatingMode},
+        simulator::SimulatorTransport,
+        supervisor::{ReconnectPolicy, Supervisor},
+        transport::{DynTransport, MemoryTransport},
+        Version,
+    };
+    use std::sync::Arc;
+
+    fn status_packet() -> Vec<u8> {
+        let mut packet = vec![0u8; 28];
+        // Length byte, offset by the leading informational byte
+        packet[3] = 30;
+        // Two hours, five minutes remaining
+        packet[4] = 2;
+        packet[5] = 5;
+        // 20C actual, 22C target
+        packet[7] = 40;
+        packet[8] = 44;
+        // Normal heat, fan step 9
+        packet[9] = 1;
+        packet[10] = 9;
+        packet
+    }
+
+    #[tokio::test]
+    async fn send_command_writes_encoded_bytes() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = BedJet::new(transport.clone());
+
+        bedjet
+            .send_command(Command::Button(ButtonCode::Heat))
+            .await
+            .unwrap();
+        bedjet
+            .send_command(Command::SetTemp(TempParam::Celsius(
+                Temperature::from_celsius(22.0).unwrap(),
+            )))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            transport.take_writes(),
+            vec![
+                (BedJet::COMMANDS_UUID, vec![0x01, 0x03]),
+                (BedJet::COMMANDS_UUID, vec![0x03, 44]),
+            ]
+        );
+    }
+
+    /// A status in cool mode, which allows 15°C to 33°C
+    fn cool_status_packet() -> Vec<u8> {
+        let mut packet = status_packet();
+        packet[9] = OperatingMode::Cool as u8;
+        packet[13] = 30;
+        packet[14] = 66;
+        packet
+    }
+
+    #[tokio::test]
+    async fn out_of_range_temperature_is_rejected() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = BedJet::new(transport.clone());
+        bedjet
+            .handle_device_status(cool_status_packet())
+            .await
+            .unwrap();
+
+        let result = bedjet
+            .send_command(Command::SetTemp(TempParam::Celsius(
+                Temperature::from_celsius(40.0).unwrap(),
+            )))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(DeviceError::OutOfRange { min, max })
+                if min.celsius() == 15.0 && max.celsius() == 33.0
+        ));
+        assert!(transport.writes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn out_of_range_temperature_is_clamped() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = BedJet::new(transport.clone());
+        bedjet.set_temperature_policy(TemperaturePolicy::Clamp);
+        bedjet
+            .handle_device_status(cool_status_packet())
+            .await
+            .unwrap();
+
+        for fahrenheit in [104, 50] {
+            bedjet
+                .send_command(Command::SetTemp(TempParam::Fahrenheit(fahrenheit)))
+                .await
+                .unwrap();
+        }
+
+        let sent: Vec<_> = transport
+            .take_writes()
+            .into_iter()
+            .map(|(_, data)| data)
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                vec![CommandClass::SetTemp as u8, 66],
+                vec![CommandClass::SetTemp as u8, 30]
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_temperature_range_is_not_enforced() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = BedJet::new(transport.clone());
+        let mut packet = cool_status_packet();
+        packet[13] = 0;
+        packet[14] = 0;
+        bedjet.handle_device_status(packet).await.unwrap();
+
+        bedjet
+            .send_command(Command::SetTemp(TempParam::Celsius(
+                Temperature::from_celsius(22.0).unwrap(),
+            )))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            transport.take_writes(),
+            vec![(BedJet::COMMANDS_UUID, vec![CommandClass::SetTemp as u8, 44])]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_characteristic_is_an_error() {
+        let bedjet = BedJet::new(MemoryTransport::new());
+
+        let result = bedjet.send_command(Command::Button(ButtonCode::Stop)).await;
+
+        assert!(matches!(
+            result,
+            Err(DeviceError::MissingCharacteristic(BedJet::COMMANDS_UUID))
+        ));
+    }
+
+    #[tokio::test]
+    async fn notifications_update_status() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+
+        let listener = tokio::spawn({
+            let bedjet = bedjet.clone();
+            async move { bedjet.handle_notifications().await }
+        });
+
+        while !transport.is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+        transport.notify(BedJet::DEVICE_STATUS_UUID, status_packet());
+
+        let status = bedjet.get_status().await.unwrap();
+        assert_eq!(status.remaining_hours, 2);
+        assert_eq!(status.remaining_minutes, 5);
+        assert_eq!(status.actual_temp.celsius(), 20.0);
+        assert_eq!(status.target_temp.celsius(), 22.0);
+        assert_eq!(status.operating_mode, OperatingMode::NormalHeat);
+        assert_eq!(status.fan_step, 9);
+
+        listener.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn get_parameter_decodes_firmware_versions() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let mut reply = vec![
+            ParameterCode::FirmwareVersionCodes as u8,
+            16,
+            3,
+            1,
+            4,
+            1,
+            5,
+            9,
+        ];
+        reply.resize(18, 0);
+        transport.set_value(BedJet::EXTENDED_DATA_UUID, reply);
+        let bedjet = BedJet::new(transport.clone());
+
+        let value = bedjet
+            .get_parameter(ParameterCode::FirmwareVersionCodes)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            value,
+            ParameterValue::FirmwareVersionCodes(vec![
+                Version {
+                    major: 3,
+                    minor: 1,
+                    patch: 4
+                },
+                Version {
+                    major: 1,
+                    minor: 5,
+                    patch: 9
+                },
+            ])
+        );
+        assert_eq!(
+            transport.writes(),
+            vec![(
+                BedJet::COMMANDS_UUID,
+                vec![0x41, ParameterCode::FirmwareVersionCodes as u8]
+            )]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn get_parameter_waits_for_a_fresh_reply() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = BedJet::new(transport.clone());
+        // Answers each request a little while after it's made, with a different name each time
+        let device = tokio::spawn({
+            let transport = transport.clone();
+            async move {
+                for name in ["Upstairs", "Downstairs"] {
+                    while transport.take_writes().is_empty() {
+                        time::sleep(Duration::from_millis(10)).await;
+                    }
+                    time::sleep(Duration::from_millis(200)).await;
+                    let reply = SetParamKind::DeviceName(String::from(name))
+                        .encode()
+                        .unwrap();
+                    transport.set_value(BedJet::EXTENDED_DATA_UUID, reply);
+                }
+            }
+        });
+
+        for expected in ["Upstairs", "Downstairs"] {
+            let value = bedjet
+                .get_parameter(ParameterCode::DeviceName)
+                .await
+                .unwrap();
+            assert_eq!(value, ParameterValue::Name(String::from(expected)));
+        }
+
+        device.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn get_parameter_times_out_without_a_matching_reply() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        // A stale reply to some other request
+        let mut reply = vec![ParameterCode::DeviceName as u8, 16];
+        reply.resize(18, 0);
+        transport.set_value(BedJet::EXTENDED_DATA_UUID, reply);
+        let bedjet = Arc::new(BedJet::new(transport));
+        bedjet.set_parameter_timeout(Duration::from_secs(5));
+
+        let started = time::Instant::now();
+        let result = bedjet.get_parameter(ParameterCode::MemoryName1).await;
+
+        assert!(matches!(result, Err(DeviceError::Timeout)));
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn commands_are_not_gated_by_firmware() {
+        let old = Version {
+            major: 2,
+            minor: 1,
+            patch: 9,
+        };
+        let mut device = SimulatedBedJet::default();
+        device.firmware = FirmwareInfo {
+            main_board: old,
+            wifi_module: None,
+        };
+        let transport = SimulatorTransport::new(device);
+        let bedjet = BedJet::new(transport.clone());
+        let ring_of_light = Command::Button(ButtonCode::EnableRingOfLight);
+
+        let firmware = bedjet.discover_firmware().await.unwrap();
+        assert_eq!(firmware.main_board, old);
+        assert_eq!(
+            firmware.check(&ring_of_light),
+            Err(crate::firmware::SETTINGS_BUTTONS_FIRMWARE)
+        );
+        transport.memory().take_writes();
+
+        // The versions commands need aren't confirmed, so older firmware only gets a warning
+        bedjet.send_command(ring_of_light.clone()).await.unwrap();
+        assert_eq!(
+            transport.memory().writes(),
+            vec![(BedJet::COMMANDS_UUID, ring_of_light.encode().unwrap())]
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_names_round_trip_through_simulator() {
+        let bedjet = BedJet::new(SimulatorTransport::default());
+
+        bedjet
+            .set_memory_name(2, String::from("Warm Up"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            bedjet.read_memory_names().await.unwrap(),
+            vec![String::new(), String::from("Warm Up"), String::new()]
+        );
+        assert!(matches!(
+            bedjet
+                .set_memory_name(1, String::from("Much too long a name"))
+                .await,
+            Err(DeviceError::InterfaceError(
+                InterfaceError::NameTooLong { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn boxed_transports_drive_the_device() {
+        let transport = SimulatorTransport::default();
+        let bedjet = BedJet::new(Arc::new(transport.clone()) as DynTransport);
+
+        bedjet.rename("Guest Room").await.unwrap();
+
+        assert_eq!(transport.device().name, "Guest Room");
+    }
+
+    #[tokio::test]
+    async fn rename_is_read_back_without_padding() {
+        let transport = SimulatorTransport::default();
+        let bedjet = BedJet::new(transport.clone());
+
+        bedjet.rename("Guest Room").await.unwrap();
+
+        assert_eq!(transport.device().name, "Guest Room");
+        assert_eq!(
+            transport
+                .memory()
+                .value(BedJet::FRIENDLY_NAME_UUID)
+                .unwrap()
+                .len(),
+            16
+        );
+        assert_eq!(bedjet.get_friendly_name().await.unwrap(), "Guest Room");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rename_fails_when_device_keeps_old_name() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        transport.set_value(BedJet::FRIENDLY_NAME_UUID, b"BedJet\0\0\0".to_vec());
+        let bedjet = BedJet::new(transport);
+
+        let result = bedjet.rename("Guest Room").await;
+
+        assert!(matches!(
+            result,
+            Err(DeviceError::NameNotApplied { actual, .. }) if actual == "BedJet"
+        ));
+    }
+
+    #[tokio::test]
+    async fn biorhythms_round_trip_through_simulator() {
+        let bedjet = BedJet::new(SimulatorTransport::default());
+
+        let programs: Vec<BiorhythmProgram> = (1..=3)
+            .map(|slot| BiorhythmProgram {
+                name: format!("Program {slot}"),
+                steps: (0..slot * 4)
+                    .map(|i| BiorhythmStep {
+                        mode: OperatingMode::NormalHeat,
+                        temperature: Temperature::from_half_degrees(60 + i),
+                        fan_step: i,
+                        hours: i / 4,
+                        minutes: i % 4 * 15,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        for (slot, program) in (1..).zip(&programs) {
+            bedjet.write_biorhythm(slot, program).await.unwrap();
+        }
+
+        assert_eq!(bedjet.read_biorhythms().await.unwrap(), programs);
+    }
+
+    async fn with_running_simulator<F: std::future::Future>(
+        transport: &SimulatorTransport,
+        bedjet: &Arc<BedJet<SimulatorTransport>>,
+        future: F,
+    ) -> F::Output {
+        let listener = tokio::spawn({
+            let bedjet = bedjet.clone();
+            async move { bedjet.handle_notifications().await }
+        });
+        while !transport.memory().is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+
+        let clock = async {
+            loop {
+                tokio::task::yield_now().await;
+                transport.advance(Duration::from_secs(1));
+            }
+        };
+        let output = tokio::select! {
+            output = future => output,
+            _ = clock => unreachable!(),
+        };
+
+        listener.abort();
+        output
+    }
+
+    #[tokio::test]
+    async fn provision_wifi_waits_for_connection_test() {
+        let transport = SimulatorTransport::default();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+
+        with_running_simulator(
+            &transport,
+            &bedjet,
+            bedjet.provision_wifi("Home", "hunter22"),
+        )
+        .await
+        .unwrap();
+
+        let device = transport.device();
+        assert_eq!(device.wifi_ssid.as_deref(), Some("Home"));
+        assert_eq!(device.wifi_password.as_deref(), Some("hunter22"));
+    }
+
+    #[tokio::test]
+    async fn provision_wifi_reports_failures() {
+        let transport = SimulatorTransport::default();
+        transport.device().wifi_reachable = false;
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+
+        let result =
+            with_running_simulator(&transport, &bedjet, bedjet.provision_wifi("Away", "pass"))
+                .await;
+
+        assert!(matches!(
+            result,
+            Err(DeviceError::ConnectionTestFailed(
+                UpdateStatus::UnableToConnect
+            ))
+        ));
+        assert!(matches!(
+            bedjet.provision_wifi(&"x".repeat(33), "pass").await,
+            Err(DeviceError::InterfaceError(
+                InterfaceError::InvalidParameter
+            ))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connection_tests_fail_without_reporting_progress() {
+        let (send, mut status) = watch::channel(None);
+        send.send_replace(Some(DeviceStatus {
+            update_status: UpdateStatus::RadioDisabled,
+            ..SimulatedBedJet::default().status()
+        }));
+
+        let outcome = time::timeout(Duration::from_secs(1), wait_for_outcome(&mut status))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(outcome, UpdateStatus::RadioDisabled);
+    }
+
+    #[tokio::test]
+    async fn update_firmware_reconnects_after_restart() {
+        let transport = SimulatorTransport::default();
+        let new_firmware = Version {
+            major: 3,
+            minor: 3,
+            patch: 0,
+        };
+        transport.device().available_firmware = Some(new_firmware);
+        transport.device().wifi_ssid = Some(String::from("Home"));
+        transport
+            .memory()
+            .set_value(BedJet::WIFI_SSID_UUID, b"Home".to_vec());
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let supervisor = Arc::new(Supervisor::new(
+            bedjet.clone(),
+            ReconnectPolicy {
+                initial_delay: Duration::ZERO,
+                ..Default::default()
+            },
+        ));
+        let supervisor_task = tokio::spawn({
+            let supervisor = supervisor.clone();
+            async move { supervisor.run().await }
+        });
+        while !transport.memory().is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+
+        let mut events = Vec::new();
+        let clock = async {
+            loop {
+                tokio::task::yield_now().await;
+                transport.advance(Duration::from_secs(1));
+            }
+        };
+        let outcome = tokio::select! {
+            outcome = bedjet.update_firmware(|event| events.push(event)) => outcome.unwrap(),
+            _ = clock => unreachable!(),
+        };
+        supervisor_task.abort();
+
+        let FirmwareUpdateOutcome::Updated(firmware) = outcome else {
+            panic!("Expected an update, got {outcome:?}");
+        };
+        assert_eq!(firmware.main_board, new_firmware);
+        assert_eq!(bedjet.firmware(), Some(firmware));
+        assert!(events.contains(&FirmwareUpdateEvent::Progress(UpdateStatus::Updating)));
+        assert_eq!(events.last(), Some(&FirmwareUpdateEvent::Reconnecting));
+    }
+
+    #[tokio::test]
+    async fn update_firmware_needs_wifi() {
+        let bedjet = BedJet::new(SimulatorTransport::default());
+
+        let result = bedjet.update_firmware(|_| {}).await;
+
+        assert!(matches!(
+            result,
+            Err(DeviceError::FirmwareUpdateFailed(
+                UpdateStatus::NoWiFiConfig
+            ))
+        ));
+    }
 }
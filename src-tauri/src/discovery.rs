@@ -0,0 +1,199 @@
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+/// A BedJet seen advertising during discovery
+pub struct DiscoveredDevice {
+    pub id: String,
+    /// The advertised name, if the device has sent one yet
+    pub name: Option<String>,
+    /// Signal strength in dBm
+    pub rssi: Option<i16>,
+    /// When the device was last heard from, in milliseconds since the Unix epoch
+    #[typeshare(serialized_as = "number")]
+    pub last_seen: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+#[serde(tag = "type", content = "content")]
+/// Changes to the list of discovered devices, as sent to the frontend
+pub enum DiscoveryEvent {
+    DeviceDiscovered(DiscoveredDevice),
+    DeviceUpdated(DiscoveredDevice),
+    /// The device with this id hasn't been heard from in a while
+    DeviceLost(String),
+}
+
+#[derive(Debug)]
+/// Keeps the list of discovered devices as advertisements come in, and forgets devices that
+/// stop advertising.
+///
+/// Expiry goes by the times passed in, so it can be driven by a timer. Only the `last_seen`
+/// shown to the frontend is read from the wall clock.
+pub struct DiscoveryTracker {
+    devices: HashMap<String, (DiscoveredDevice, Instant)>,
+    expiry: Duration,
+}
+
+impl DiscoveryTracker {
+    pub const DEFAULT_EXPIRY: Duration = Duration::from_secs(30);
+
+    /// Devices not heard from for `expiry` are reported lost
+    pub fn new(expiry: Duration) -> Self {
+        Self {
+            devices: HashMap::new(),
+            expiry,
+        }
+    }
+
+    /// Records an advertisement. Returns the event to send, or `None` if nothing the frontend
+    /// shows has changed. A missing name or RSSI keeps the one seen before
+    pub fn seen(
+        &mut self,
+        id: &str,
+        name: Option<String>,
+        rssi: Option<i16>,
+        now: Instant,
+    ) -> Option<DiscoveryEvent> {
+        let last_seen = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let Some((device, seen_at)) = self.devices.get_mut(id) else {
+            let device = DiscoveredDevice {
+                id: id.to_owned(),
+                name,
+                rssi,
+                last_seen,
+            };
+            self.devices.insert(id.to_owned(), (device.clone(), now));
+            return Some(DiscoveryEvent::DeviceDiscovered(device));
+        };
+
+        *seen_at = now;
+        device.last_seen = last_seen;
+        let changed =
+            (name.is_some() && name != device.name) || (rssi.is_some() && rssi != device.rssi);
+        if !changed {
+            return None;
+        }
+        device.name = name.or(device.name.take());
+        device.rssi = rssi.or(device.rssi);
+        Some(DiscoveryEvent::DeviceUpdated(device.clone()))
+    }
+
+    /// Forgets devices that haven't been seen within the expiry. Devices stop advertising while
+    /// they're connected, so those in `connected` are kept, and expire a full expiry after they
+    /// disconnect
+    pub fn expire(&mut self, now: Instant, connected: &HashSet<String>) -> Vec<DiscoveryEvent> {
+        let expiry = self.expiry;
+        let mut lost = Vec::new();
+        self.devices.retain(|id, (_, seen_at)| {
+            if connected.contains(id) {
+                *seen_at = now;
+                return true;
+            }
+            let keep = now.saturating_duration_since(*seen_at) < expiry;
+            if !keep {
+                lost.push(DiscoveryEvent::DeviceLost(id.clone()));
+            }
+            keep
+        });
+        lost
+    }
+
+    /// Every device currently known, in no particular order
+    pub fn devices(&self) -> Vec<DiscoveredDevice> {
+        self.devices
+            .values()
+            .map(|(device, _)| device.clone())
+            .collect()
+    }
+}
+
+impl Default for DiscoveryTracker {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_EXPIRY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_new_and_changed_devices() {
+        let mut tracker = DiscoveryTracker::default();
+        let now = Instant::now();
+
+        let discovered = tracker.seen("a", None, Some(-70), now);
+        assert!(matches!(
+            discovered,
+            Some(DiscoveryEvent::DeviceDiscovered(DiscoveredDevice {
+                rssi: Some(-70),
+                ..
+            }))
+        ));
+
+        // Nothing new to show
+        assert_eq!(tracker.seen("a", None, Some(-70), now), None);
+        assert_eq!(tracker.seen("a", None, None, now), None);
+
+        let Some(DiscoveryEvent::DeviceUpdated(device)) =
+            tracker.seen("a", Some(String::from("Upstairs")), None, now)
+        else {
+            panic!("Expected an update");
+        };
+        assert_eq!(device.name.as_deref(), Some("Upstairs"));
+        assert_eq!(device.rssi, Some(-70));
+    }
+
+    #[test]
+    fn expires_devices_that_stop_advertising() {
+        let mut tracker = DiscoveryTracker::new(Duration::from_secs(10));
+        let start = Instant::now();
+        tracker.seen("a", None, None, start);
+        tracker.seen("b", None, None, start);
+        tracker.seen("b", None, None, start + Duration::from_secs(8));
+
+        let lost = tracker.expire(start + Duration::from_secs(12), &HashSet::new());
+
+        assert_eq!(lost, vec![DiscoveryEvent::DeviceLost(String::from("a"))]);
+        assert_eq!(tracker.devices().len(), 1);
+        assert_eq!(
+            tracker.expire(start + Duration::from_secs(12), &HashSet::new()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn connected_devices_are_kept_until_they_disconnect() {
+        let mut tracker = DiscoveryTracker::new(Duration::from_secs(10));
+        let start = Instant::now();
+        tracker.seen("a", None, None, start);
+        let connected = HashSet::from([String::from("a")]);
+
+        assert_eq!(
+            tracker.expire(start + Duration::from_secs(60), &connected),
+            vec![]
+        );
+
+        // Disconnected at 60 seconds, so it has until 70
+        let none = HashSet::new();
+        assert_eq!(
+            tracker.expire(start + Duration::from_secs(65), &none),
+            vec![]
+        );
+        assert_eq!(
+            tracker.expire(start + Duration::from_secs(70), &none),
+            vec![DiscoveryEvent::DeviceLost(String::from("a"))]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    device::DeviceError, registry::RegistryError, schedule::ScheduleError, store::StoreError,
+    thermostat::SourceError, InterfaceError,
+};
+use serde::Serialize;
+use thiserror::Error;
+use typeshare::typeshare;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+#[serde(tag = "type", content = "content")]
+/// The errors returned to the frontend from Tauri commands.
+///
+/// Underlying errors are carried as their messages, since they can't be serialized themselves.
+pub enum AppError {
+    #[error("No Bluetooth adapter is available")]
+    AdapterNotFound,
+    #[error("Bluetooth adapter {0} isn't responding, it may be powered off")]
+    AdapterUnavailable(String),
+    #[error("No BedJet found with id {0}")]
+    DeviceNotFound(String),
+    #[error("No group named {0}")]
+    GroupNotFound(String),
+    #[error("The BedJet isn't connected")]
+    NotConnected,
+    #[error("The BedJet doesn't have characteristic {0}")]
+    MissingCharacteristic(String),
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    #[error("Bluetooth error: {0}")]
+    Bluetooth(String),
+    #[error("Timed out waiting for the BedJet to respond")]
+    Timeout,
+    /// The known devices couldn't be changed
+    #[error("{0}")]
+    Registry(String),
+    #[error("{0}")]
+    InvalidSchedule(String),
+    /// Saved settings couldn't be read or written
+    #[error("{0}")]
+    Storage(String),
+    /// The device is reachable, but refused or failed what was asked of it
+    #[error("{0}")]
+    Device(String),
+    /// A temperature sensor couldn't be read
+    #[error("{0}")]
+    Sensor(String),
+}
+
+impl From<InterfaceError> for AppError {
+    fn from(value: InterfaceError) -> Self {
+        Self::Protocol(value.to_string())
+    }
+}
+
+impl From<btleplug::Error> for AppError {
+    fn from(value: btleplug::Error) -> Self {
+        match value {
+            btleplug::Error::NotConnected => Self::NotConnected,
+            btleplug::Error::TimedOut(_) => Self::Timeout,
+            other => Self::Bluetooth(other.to_string()),
+        }
+    }
+}
+
+impl From<DeviceError> for AppError {
+    fn from(value: DeviceError) -> Self {
+        match value {
+            DeviceError::InterfaceError(error) => error.into(),
+            DeviceError::BluetoothError(error) => error.into(),
+            DeviceError::MissingCharacteristic(uuid) => {
+                Self::MissingCharacteristic(uuid.to_string())
+            }
+            DeviceError::Timeout => Self::Timeout,
+            other => Self::Device(other.to_string()),
+        }
+    }
+}
+
+impl From<RegistryError> for AppError {
+    fn from(value: RegistryError) -> Self {
+        Self::Registry(value.to_string())
+    }
+}
+
+impl From<ScheduleError> for AppError {
+    fn from(value: ScheduleError) -> Self {
+        Self::InvalidSchedule(value.to_string())
+    }
+}
+
+impl From<StoreError> for AppError {
+    fn from(value: StoreError) -> Self {
+        Self::Storage(value.to_string())
+    }
+}
+
+impl From<SourceError> for AppError {
+    fn from(value: SourceError) -> Self {
+        Self::Sensor(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_with_type_tag() {
+        let error = AppError::from(DeviceError::Timeout);
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"type":"Timeout"}"#
+        );
+
+        let error = AppError::from(InterfaceError::UnknownButtonCode(0x99));
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"type":"Protocol","content":"Unknown button code 0x99"}"#
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    proto::{ButtonCode, UpdateStatus},
+    Command, InterfaceError, ParameterValue, SetParamKind, Version,
+};
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[typeshare]
+/// The firmware running on the device, as reported by `ParameterCode::FirmwareVersionCodes`
+pub struct FirmwareInfo {
+    pub main_board: Version,
+    /// Only present on devices with a Wi-Fi module
+    pub wifi_module: Option<Version>,
+}
+
+impl TryFrom<ParameterValue> for FirmwareInfo {
+    type Error = InterfaceError;
+
+    fn try_from(value: ParameterValue) -> Result<Self, Self::Error> {
+        let ParameterValue::FirmwareVersionCodes(versions) = value else {
+            return Err(InterfaceError::InvalidParameter);
+        };
+        let mut versions = versions.into_iter();
+
+        Ok(Self {
+            main_board: versions.next().ok_or(InterfaceError::NotEnoughData)?,
+            wifi_module: versions.next(),
+        })
+    }
+}
+
+const fn version(major: u8, minor: u8, patch: u8) -> Version {
+    Version {
+        major,
+        minor,
+        patch,
+    }
+}
+
+/// The oldest main board firmware thought to handle the ring of light and power mode buttons.
+/// There's no published changelog to confirm it against, so it's only used for warnings
+pub const SETTINGS_BUTTONS_FIRMWARE: Version = version(2, 2, 0);
+/// The oldest main board firmware thought to store biorhythm programs. Unconfirmed, like
+/// [`SETTINGS_BUTTONS_FIRMWARE`]
+pub const BIORHYTHM_FIRMWARE: Version = version(3, 0, 0);
+
+impl FirmwareInfo {
+    /// The main board firmware a command needs, if it doesn't work on every device
+    pub fn required_for(command: &Command) -> Option<Version> {
+        match command {
+            Command::Button(
+                ButtonCode::EnableRingOfLight
+                | ButtonCode::DisableRingOfLight
+                | ButtonCode::SetLowPowerMode
+                | ButtonCode::SetNormalPowerMode,
+            ) => Some(SETTINGS_BUTTONS_FIRMWARE),
+            Command::SetParam(
+                SetParamKind::BiorhythmName { .. } | SetParamKind::BiorhythmFragment { .. },
+            ) => Some(BIORHYTHM_FIRMWARE),
+            Command::GetParam(code) if code.biorhythm_slot().is_some() => Some(BIORHYTHM_FIRMWARE),
+            _ => None,
+        }
+    }
+
+    /// Checks a command against this firmware, returning the version it needs if it may not be
+    /// supported
+    pub fn check(&self, command: &Command) -> Result<(), Version> {
+        match Self::required_for(command) {
+            Some(required) if self.main_board < required => Err(required),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[typeshare]
+#[serde(tag = "type", content = "content")]
+/// How a firmware update is going
+pub enum FirmwareUpdateEvent {
+    /// The device reported a new status
+    Progress(UpdateStatus),
+    /// The device restarted into the new firmware, and is being reconnected to
+    Reconnecting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[typeshare]
+#[serde(tag = "type", content = "content")]
+/// How a firmware update ended, when it didn't fail
+pub enum FirmwareUpdateOutcome {
+    /// The device already had the latest firmware
+    UpToDate,
+    /// The device restarted with this firmware
+    Updated(FirmwareInfo),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+/// A firmware update event for a specific device, as sent to the frontend
+pub struct FirmwareUpdateProgressEvent {
+    pub id: String,
+    pub event: FirmwareUpdateEvent,
+}
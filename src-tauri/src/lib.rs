@@ -1,16 +1,36 @@
+use chrono::{NaiveTime, Timelike};
 use num_traits::FromPrimitive;
 use proto::{ButtonCode, CommandClass, DeviceStatus, ParameterCode};
 use serde::{Deserialize, Serialize};
 use std::{
+    fmt,
     io::{self, Read},
     time::Duration,
 };
 use thiserror::Error;
 use typeshare::typeshare;
 
-use crate::proto::{OperatingMode, ShutDownCode, UpdateStatus};
+use crate::proto::{OperatingMode, ShutDownCode, TemperatureUnit, UpdateStatus};
+use crate::temperature::Temperature;
+pub mod adapters;
+pub mod biorhythm;
+pub mod clock;
 pub mod device;
+pub mod discovery;
+pub mod error;
+pub mod firmware;
+pub mod manager;
 pub mod proto;
+pub mod registry;
+pub mod schedule;
+pub mod settings;
+pub mod simulator;
+pub mod store;
+pub mod supervisor;
+pub mod sync;
+pub mod temperature;
+pub mod thermostat;
+pub mod transport;
 
 pub trait Encode
 where
@@ -29,12 +49,59 @@ pub trait Decode: Sized {
     fn read_from<R: Read>(reader: R) -> Result<Self, InterfaceError>;
 }
 
+/// Fills the buffer, treating the end of the data as there not being enough of it
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), InterfaceError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => InterfaceError::NotEnoughData,
+        _ => err.into(),
+    })
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> Result<u8, InterfaceError> {
+    let mut byte = [0u8; 1];
+    read_exact(reader, &mut byte)?;
+    Ok(byte[0])
+}
+
+impl DeviceStatus {
+    /// The size of a status packet, not counting the leading informational byte
+    pub const PACKET_LEN: usize = 27;
+    /// Where in the packet each of the `unknown` bytes lives
+    const UNKNOWN_POSITIONS: [usize; 10] = [14, 15, 18, 19, 20, 21, 22, 23, 24, 26];
+
+    /// The runtime left on the device
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs(
+            self.remaining_hours as u64 * 3600
+                + self.remaining_minutes as u64 * 60
+                + self.remaining_seconds as u64,
+        )
+    }
+
+    /// The step of the biorhythm program that's running, 0 if none is, going by byte 14
+    pub fn biorhythm_step(&self) -> u8 {
+        self.unknown[0]
+    }
+
+    /// The units the device displays temperatures in, going by byte 26, if it holds one
+    pub fn units(&self) -> Option<TemperatureUnit> {
+        TemperatureUnit::from_u8(self.unknown[9])
+    }
+}
+
 impl Decode for DeviceStatus {
     fn read_from<R: Read>(mut reader: R) -> Result<Self, InterfaceError> {
-        let mut packet = [0u8; 27];
-        let read_bytes = reader.read(&mut packet)?;
+        let mut packet = [0u8; Self::PACKET_LEN];
+        // The packet may be split across several sources, so keep reading until it's full
+        let mut read_bytes = 0;
+        while read_bytes < packet.len() {
+            match reader.read(&mut packet[read_bytes..])? {
+                0 => break,
+                n => read_bytes += n,
+            }
+        }
 
-        if read_bytes as u8 != packet[2] - 3 {
+        if packet[2].checked_sub(3) != Some(read_bytes as u8) {
             return Err(InterfaceError::NotEnoughData);
         }
 
@@ -45,25 +112,60 @@ impl Decode for DeviceStatus {
         let update_status =
             UpdateStatus::from_u8(packet[25]).ok_or_else(|| InterfaceError::InvalidParameter)?;
 
+        let mut unknown = [0u8; 10];
+        for (byte, position) in unknown.iter_mut().zip(Self::UNKNOWN_POSITIONS) {
+            *byte = packet[position];
+        }
+
         Ok(Self {
             remaining_hours: packet[3],
             remaining_minutes: packet[4],
             remaining_seconds: packet[5],
-            actual_temp: packet[6],
-            target_temp: packet[7],
+            actual_temp: Temperature::from_half_degrees(packet[6]),
+            target_temp: Temperature::from_half_degrees(packet[7]),
             operating_mode,
             fan_step: packet[9],
             max_duration_hours: packet[10],
             max_duration_minutes: packet[11],
-            min_target_temp: packet[12],
-            max_target_temp: packet[13],
-            ambient_temp: packet[16],
+            min_target_temp: Temperature::from_half_degrees(packet[12]),
+            max_target_temp: Temperature::from_half_degrees(packet[13]),
+            ambient_temp: Temperature::from_half_degrees(packet[16]),
             shutdown_code,
             update_status,
+            unknown,
         })
     }
 }
 
+impl Encode for DeviceStatus {
+    fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), InterfaceError> {
+        let mut packet = [0u8; Self::PACKET_LEN];
+        // The length byte counts 3 more than the packet actually holds
+        packet[2] = Self::PACKET_LEN as u8 + 3;
+        packet[3] = self.remaining_hours;
+        packet[4] = self.remaining_minutes;
+        packet[5] = self.remaining_seconds;
+        packet[6] = self.actual_temp.half_degrees();
+        packet[7] = self.target_temp.half_degrees();
+        packet[8] = self.operating_mode as u8;
+        packet[9] = self.fan_step;
+        packet[10] = self.max_duration_hours;
+        packet[11] = self.max_duration_minutes;
+        packet[12] = self.min_target_temp.half_degrees();
+        packet[13] = self.max_target_temp.half_degrees();
+        packet[16] = self.ambient_temp.half_degrees();
+        packet[17] = self.shutdown_code as u8;
+        packet[25] = self.update_status as u8;
+
+        for (byte, position) in self.unknown.iter().zip(Self::UNKNOWN_POSITIONS) {
+            packet[position] = *byte;
+        }
+
+        writer.write_all(&packet)?;
+        Ok(())
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum InterfaceError {
     #[error("Invalid Data provided to protocol")]
@@ -72,6 +174,14 @@ pub enum InterfaceError {
     IOError(#[from] io::Error),
     #[error("Invalid Data provided to protocol")]
     NotEnoughData,
+    #[error("Unknown command class {0:#04x}")]
+    UnknownCommandClass(u8),
+    #[error("Unknown button code {0:#04x}")]
+    UnknownButtonCode(u8),
+    #[error("Unknown parameter code {0:#04x}")]
+    UnknownParameterCode(u8),
+    #[error("Name is {len} bytes long, but names can be at most {max} bytes")]
+    NameTooLong { len: usize, max: usize },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -85,6 +195,8 @@ pub enum Command {
     SetFan(FanParam),
     SetClock { hours: u8, minutes: u8 },
     SetParam(SetParamKind),
+    /// Asks the device to put the value of a parameter in the extended data characteristic
+    GetParam(ParameterCode),
 }
 
 impl Encode for Command {
@@ -105,45 +217,139 @@ impl Encode for Command {
                 fan.write_to(writer)?
             }
             Command::SetClock { hours, minutes } => {
+                if *hours > 23 || *minutes > 59 {
+                    return Err(InterfaceError::InvalidParameter);
+                }
                 writer.write_all(&[CommandClass::SetClock as u8, *hours, *minutes])?
             }
             Command::SetParam(param) => {
                 writer.write_all(&[CommandClass::SetParameter as u8])?;
                 param.write_to(writer)?
             }
+            Command::GetParam(code) => {
+                writer.write_all(&[CommandClass::GetParameter as u8, *code as u8])?
+            }
         };
 
         Ok(())
     }
 }
 
+impl Command {
+    /// Sets the device's clock to a time of day
+    pub fn set_clock(time: NaiveTime) -> Self {
+        Command::SetClock {
+            hours: time.hour() as u8,
+            minutes: time.minute() as u8,
+        }
+    }
+}
+
+impl Decode for Command {
+    fn read_from<R: Read>(mut reader: R) -> Result<Self, InterfaceError> {
+        let class = read_byte(&mut reader)?;
+        let class = CommandClass::from_u8(class).ok_or(InterfaceError::UnknownCommandClass(class))?;
+
+        let command = match class {
+            CommandClass::Button => {
+                let code = read_byte(&mut reader)?;
+                Command::Button(
+                    ButtonCode::from_u8(code).ok_or(InterfaceError::UnknownButtonCode(code))?,
+                )
+            }
+            CommandClass::SetTime => Command::SetTime {
+                hours: read_byte(&mut reader)?,
+                minutes: read_byte(&mut reader)?,
+            },
+            CommandClass::SetTemp => Command::SetTemp(TempParam::read_from(reader)?),
+            CommandClass::SetFan => Command::SetFan(FanParam::read_from(reader)?),
+            CommandClass::SetClock => Command::SetClock {
+                hours: read_byte(&mut reader)?,
+                minutes: read_byte(&mut reader)?,
+            },
+            CommandClass::SetParameter => Command::SetParam(SetParamKind::read_from(reader)?),
+            CommandClass::GetParameter => {
+                let code = read_byte(&mut reader)?;
+                Command::GetParam(
+                    ParameterCode::from_u8(code).ok_or(InterfaceError::UnknownParameterCode(code))?,
+                )
+            }
+        };
+
+        Ok(command)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Button(code) => write!(f, "Press {code:?}"),
+            Command::SetTime { hours, minutes } => {
+                write!(f, "Set runtime to {hours}h {minutes:02}m")
+            }
+            Command::SetTemp(TempParam::Celsius(val)) => write!(f, "Set temperature to {val}"),
+            Command::SetTemp(TempParam::Fahrenheit(val)) => {
+                write!(f, "Set temperature to {val}°F")
+            }
+            Command::SetFan(FanParam::Step(val)) => write!(f, "Set fan to step {val}"),
+            Command::SetFan(FanParam::Percent(val)) => write!(f, "Set fan to {val}%"),
+            Command::SetClock { hours, minutes } => {
+                write!(f, "Set clock to {hours:02}:{minutes:02}")
+            }
+            Command::SetParam(SetParamKind::DeviceName(name)) => {
+                write!(f, "Set device name to {name:?}")
+            }
+            Command::SetParam(SetParamKind::MemoryName { slot, name }) => {
+                write!(f, "Set memory {slot} name to {name:?}")
+            }
+            Command::SetParam(SetParamKind::BiorhythmName { slot, name }) => {
+                write!(f, "Set biorhythm {slot} name to {name:?}")
+            }
+            Command::SetParam(SetParamKind::BiorhythmFragment { slot, fragment, .. }) => {
+                write!(f, "Set biorhythm {slot} fragment {fragment}")
+            }
+            Command::GetParam(code) => write!(f, "Get {code:?}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(tag = "type", content = "value")]
 #[typeshare]
 pub enum TempParam {
-    /// The temperature in degrees Celsius
-    Celsius(u8),
-    /// The temperature in degrees Fahrenheit
+    /// The temperature in degrees Celsius, with half degree precision
+    Celsius(Temperature),
+    /// The temperature in whole degrees Fahrenheit
     Fahrenheit(u8),
 }
 
+impl TempParam {
+    /// The temperature the device will be sent.
+    /// Fahrenheit is converted to the closest half degree Celsius
+    pub fn temperature(&self) -> Result<Temperature, InterfaceError> {
+        match self {
+            TempParam::Celsius(val) => Ok(*val),
+            TempParam::Fahrenheit(val) => Temperature::from_fahrenheit(*val as f32),
+        }
+    }
+}
+
 impl Encode for TempParam {
     fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), InterfaceError> {
-        // The actual value we need to write is stored in units of 0.5 Celsius, so we multiply by 2
-        // or convert to Celsius and multiply by 2
-        let value = match self {
-            TempParam::Celsius(val) => val.saturating_mul(2),
-            TempParam::Fahrenheit(val) => val
-                .saturating_sub(32)
-                .saturating_mul(5)
-                .saturating_div(9)
-                .saturating_mul(2),
-        };
-        writer.write_all(&[value])?;
+        writer.write_all(&[self.temperature()?.half_degrees()])?;
         Ok(())
     }
 }
 
+impl Decode for TempParam {
+    fn read_from<R: Read>(mut reader: R) -> Result<Self, InterfaceError> {
+        // The device only deals in Celsius, in units of 0.5 degrees
+        Ok(TempParam::Celsius(Temperature::from_half_degrees(
+            read_byte(&mut reader)?,
+        )))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(tag = "type", content = "value")]
 #[typeshare]
@@ -160,47 +366,252 @@ impl FanParam {
             _ => Ok(()),
         }
     }
-}
 
-impl Encode for FanParam {
-    fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), InterfaceError> {
+    /// The fan step the device expects, from 0 to 19
+    pub fn step(&self) -> Result<u8, InterfaceError> {
         self.validate()?;
 
-        let value = match self {
+        Ok(match self {
             FanParam::Step(val) => *val,
             FanParam::Percent(val) => val.saturating_div(5).saturating_sub(1),
-        };
+        })
+    }
+}
 
-        writer.write_all(&[value])?;
+impl Encode for FanParam {
+    fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), InterfaceError> {
+        writer.write_all(&[self.step()?])?;
 
         Ok(())
     }
 }
+
+impl Decode for FanParam {
+    fn read_from<R: Read>(mut reader: R) -> Result<Self, InterfaceError> {
+        // The device only deals in steps
+        let fan = FanParam::Step(read_byte(&mut reader)?);
+        fan.validate()?;
+        Ok(fan)
+    }
+}
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SetParamKind {
     /// Cannot contain a String longer than 15 bytes.
     DeviceName(String),
+    /// The name of memory slot 1 to 3. Cannot contain a String longer than 15 bytes.
+    MemoryName { slot: u8, name: String },
+    /// The name of biorhythm slot 1 to 3. Cannot contain a String longer than 15 bytes.
+    BiorhythmName { slot: u8, name: String },
+    /// One of the four fragments, numbered 1 to 4, making up the program in biorhythm slot 1 to 3
+    BiorhythmFragment {
+        slot: u8,
+        fragment: u8,
+        data: [u8; biorhythm::FRAGMENT_LEN],
+    },
+}
+
+/// Every parameter value is sent as 16 bytes
+pub const PARAM_LEN: usize = 16;
+
+impl SetParamKind {
+    pub fn code(&self) -> Result<ParameterCode, InterfaceError> {
+        let code = match self {
+            SetParamKind::DeviceName(_) => Some(ParameterCode::DeviceName),
+            SetParamKind::MemoryName { slot, .. } => ParameterCode::memory_name(*slot),
+            SetParamKind::BiorhythmName { slot, .. } => ParameterCode::biorhythm_name(*slot),
+            SetParamKind::BiorhythmFragment { slot, fragment, .. } => {
+                ParameterCode::biorhythm_fragment(*slot, *fragment)
+            }
+        };
+        code.ok_or(InterfaceError::InvalidParameter)
+    }
+
+    /// The parameter as the device stores it
+    pub fn parameter(&self) -> Result<Parameter, InterfaceError> {
+        let value = match self {
+            SetParamKind::DeviceName(name)
+            | SetParamKind::MemoryName { name, .. }
+            | SetParamKind::BiorhythmName { name, .. } => ParameterValue::Name(name.clone()),
+            SetParamKind::BiorhythmFragment { data, .. } => {
+                ParameterValue::BiorhythmFragment(*data)
+            }
+        };
+        Ok(Parameter {
+            code: self.code()?,
+            value,
+        })
+    }
+}
+
+impl TryFrom<Parameter> for SetParamKind {
+    type Error = InterfaceError;
+
+    /// Fails for parameters that can't be set
+    fn try_from(Parameter { code, value }: Parameter) -> Result<Self, Self::Error> {
+        match (value, code.memory_slot(), code.biorhythm_slot()) {
+            (ParameterValue::Name(name), _, _) if code == ParameterCode::DeviceName => {
+                Ok(SetParamKind::DeviceName(name))
+            }
+            (ParameterValue::Name(name), Some(slot), _) => {
+                Ok(SetParamKind::MemoryName { slot, name })
+            }
+            (ParameterValue::Name(name), _, Some((slot, None))) => {
+                Ok(SetParamKind::BiorhythmName { slot, name })
+            }
+            (ParameterValue::BiorhythmFragment(data), _, Some((slot, Some(fragment)))) => {
+                Ok(SetParamKind::BiorhythmFragment {
+                    slot,
+                    fragment,
+                    data,
+                })
+            }
+            _ => Err(InterfaceError::InvalidParameter),
+        }
+    }
+}
+
+/// The longest name the device stores, in bytes, leaving room for at least one zero
+pub const MAX_NAME_LEN: usize = PARAM_LEN - 1;
+
+/// Checks a name fits on the device. The limit is in bytes, so names with multibyte characters
+/// fit fewer characters
+pub fn validate_name(name: &str) -> Result<(), InterfaceError> {
+    if name.len() > MAX_NAME_LEN {
+        return Err(InterfaceError::NameTooLong {
+            len: name.len(),
+            max: MAX_NAME_LEN,
+        });
+    }
+    // The device would read a zero as the end of the name
+    if name.contains('\0') {
+        return Err(InterfaceError::InvalidParameter);
+    }
+    Ok(())
+}
+
+/// Writes a name zero padded out to the full parameter length
+fn write_name<W: io::Write>(name: &str, writer: &mut W) -> Result<(), InterfaceError> {
+    validate_name(name)?;
+    writer.write_all(name.as_bytes())?;
+
+    // Calculate the number of bytes to zero pad with
+    let padding = PARAM_LEN - name.len();
+
+    // And write those bytes out
+    io::copy(&mut io::repeat(0).take(padding as u64), writer)?;
+    Ok(())
+}
+
+fn read_name(mut value: Vec<u8>) -> Result<String, InterfaceError> {
+    // Names are zero padded out to the full length
+    let end = value.iter().position(|b| *b == 0).unwrap_or(value.len());
+    value.truncate(end);
+    String::from_utf8(value).map_err(|_| InterfaceError::InvalidParameter)
 }
 
 impl Encode for SetParamKind {
     fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), InterfaceError> {
-        match self {
-            SetParamKind::DeviceName(name) => {
-                // Validate that the string is within the allowed limit
-                if name.len() > 15 {
-                    return Err(InterfaceError::InvalidParameter);
-                }
-                // Write the header data
-                writer.write_all(&[ParameterCode::DeviceName as u8, 0x10])?;
-                // And then write the string
-                writer.write_all(name.as_bytes())?;
+        self.parameter()?.write_to(writer)
+    }
+}
+
+impl Decode for SetParamKind {
+    fn read_from<R: Read>(reader: R) -> Result<Self, InterfaceError> {
+        Parameter::read_from(reader)?.try_into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[typeshare]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+/// The decoded value of a parameter read back from the device
+pub enum ParameterValue {
+    /// The device, memory slot or biorhythm names
+    Name(String),
+    /// One version for each firmware image on the device, main board first
+    FirmwareVersionCodes(Vec<Version>),
+    /// Raw biorhythm program data, see [`biorhythm::BiorhythmProgram::from_fragments`]
+    BiorhythmFragment([u8; biorhythm::FRAGMENT_LEN]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A parameter as it appears in the extended data characteristic
+pub struct Parameter {
+    pub code: ParameterCode,
+    pub value: ParameterValue,
+}
+
+impl Decode for Parameter {
+    fn read_from<R: Read>(mut reader: R) -> Result<Self, InterfaceError> {
+        let code = read_byte(&mut reader)?;
+        let code = ParameterCode::from_u8(code).ok_or(InterfaceError::UnknownParameterCode(code))?;
+        let len = read_byte(&mut reader)?;
 
-                // Calculate the number of bytes to zero pad with
-                let padding = 16 - name.len();
+        let mut value = vec![0u8; len as usize];
+        read_exact(&mut reader, &mut value)?;
 
-                // And write those bytes out
-                io::copy(&mut io::repeat(0).take(padding as u64), writer)?;
+        let value = match code {
+            // Taken to be one major, minor, patch triple per image. That hasn't been confirmed
+            // against a real device, so nothing is refused because of what it says
+            ParameterCode::FirmwareVersionCodes => ParameterValue::FirmwareVersionCodes(
+                value
+                    .chunks_exact(3)
+                    // Unused entries are zeroed out
+                    .take_while(|code| code.iter().any(|b| *b != 0))
+                    .map(|code| Version {
+                        major: code[0],
+                        minor: code[1],
+                        patch: code[2],
+                    })
+                    .collect(),
+            ),
+            _ if code.biorhythm_slot().is_some_and(|(_, fragment)| fragment.is_some()) => {
+                ParameterValue::BiorhythmFragment(
+                    value
+                        .try_into()
+                        .map_err(|_| InterfaceError::InvalidParameter)?,
+                )
             }
+            // Everything else is a name
+            _ => ParameterValue::Name(read_name(value)?),
+        };
+
+        Ok(Self { code, value })
+    }
+}
+
+impl Encode for Parameter {
+    fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), InterfaceError> {
+        // Write the header data
+        writer.write_all(&[self.code as u8, PARAM_LEN as u8])?;
+
+        // And then write the value
+        match &self.value {
+            ParameterValue::Name(name) => write_name(name, writer)?,
+            ParameterValue::FirmwareVersionCodes(versions) => {
+                if versions.len() > PARAM_LEN / 3 {
+                    return Err(InterfaceError::InvalidParameter);
+                }
+                let mut value = [0u8; PARAM_LEN];
+                for (version, out) in versions.iter().zip(value.chunks_exact_mut(3)) {
+                    out.copy_from_slice(&[version.major, version.minor, version.patch]);
+                }
+                writer.write_all(&value)?;
+            }
+            ParameterValue::BiorhythmFragment(data) => writer.write_all(data)?,
         }
         Ok(())
     }
@@ -208,44 +619,242 @@ impl Encode for SetParamKind {
 
 pub struct ParsedDeviceStatus {
     remaining_duration: Duration,
-    /// As degrees C
-    actual_temp: f32,
-    /// As degrees C
-    target_temp: f32,
+    actual_temp: Temperature,
+    target_temp: Temperature,
     operating_mode: OperatingMode,
     ///As a percent 0 - 100
     fan_step: u8,
     max_duration: Duration,
-    min_target_temp: f32,
-    max_target_temp: f32,
-    ambient_temp: f32,
+    min_target_temp: Temperature,
+    max_target_temp: Temperature,
+    ambient_temp: Temperature,
     shutdown_code: ShutDownCode,
     update_status: UpdateStatus,
+    biorhythm_step: u8,
+    units: Option<TemperatureUnit>,
 }
 
 impl From<DeviceStatus> for ParsedDeviceStatus {
     fn from(value: DeviceStatus) -> Self {
-        let remaining_duration = Duration::from_secs(
-            (value.remaining_hours as u64 * 3600)
-                + (value.remaining_minutes as u64 * 60)
-                + (value.remaining_seconds as u64),
-        );
+        let remaining_duration = value.remaining();
 
         let max_duration = Duration::from_secs(
             (value.max_duration_hours as u64 * 3600) + value.max_duration_minutes as u64 * 60,
         );
         Self {
             remaining_duration,
-            actual_temp: value.actual_temp as f32 / 2.0,
-            target_temp: value.target_temp as f32 / 2.0,
+            actual_temp: value.actual_temp,
+            target_temp: value.target_temp,
             operating_mode: value.operating_mode,
             fan_step: value.fan_step.saturating_add(1).saturating_mul(5),
             max_duration,
-            min_target_temp: value.min_target_temp as f32 / 2.0,
-            max_target_temp: value.max_target_temp as f32 / 2.0,
-            ambient_temp: value.ambient_temp as f32 / 2.0,
+            min_target_temp: value.min_target_temp,
+            max_target_temp: value.max_target_temp,
+            ambient_temp: value.ambient_temp,
             shutdown_code: value.shutdown_code,
             update_status: value.update_status,
+            biorhythm_step: value.biorhythm_step(),
+            units: value.units(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all<T: FromPrimitive>() -> Vec<T> {
+        (0..=u8::MAX).filter_map(T::from_u8).collect()
+    }
+
+    fn sample_status() -> DeviceStatus {
+        DeviceStatus {
+            remaining_hours: 3,
+            remaining_minutes: 59,
+            remaining_seconds: 12,
+            actual_temp: Temperature::from_half_degrees(61),
+            target_temp: Temperature::from_half_degrees(72),
+            operating_mode: OperatingMode::NormalHeat,
+            fan_step: 11,
+            max_duration_hours: 4,
+            max_duration_minutes: 0,
+            min_target_temp: Temperature::from_half_degrees(38),
+            max_target_temp: Temperature::from_half_degrees(87),
+            ambient_temp: Temperature::from_half_degrees(42),
+            shutdown_code: ShutDownCode::Normal,
+            update_status: UpdateStatus::Idle,
+            unknown: [2, 1, 2, 3, 4, 5, 6, 7, 8, 1],
+        }
+    }
+
+    #[test]
+    fn device_status_round_trips() {
+        let modes: Vec<OperatingMode> = all();
+        let shutdown_codes: Vec<ShutDownCode> = all();
+        let update_statuses: Vec<UpdateStatus> = all();
+        assert_eq!(modes.len(), 7);
+        assert_eq!(shutdown_codes.len(), 8);
+        assert_eq!(update_statuses.len(), 18);
+
+        for operating_mode in &modes {
+            for shutdown_code in &shutdown_codes {
+                for update_status in &update_statuses {
+                    let status = DeviceStatus {
+                        operating_mode: *operating_mode,
+                        shutdown_code: *shutdown_code,
+                        update_status: *update_status,
+                        ..sample_status()
+                    };
+
+                    let packet = status.encode().unwrap();
+                    assert_eq!(packet.len(), DeviceStatus::PACKET_LEN);
+                    assert_eq!(packet[2] as usize, DeviceStatus::PACKET_LEN + 3);
+                    assert_eq!(DeviceStatus::read_from(packet.as_slice()).unwrap(), status);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn device_status_keeps_unknown_bytes_in_place() {
+        let packet = sample_status().encode().unwrap();
+
+        assert_eq!(packet[14], 2);
+        assert_eq!(packet[15], 1);
+        assert_eq!(&packet[18..=24], &[2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(packet[26], 1);
+    }
+
+    #[test]
+    fn device_status_decodes_biorhythm_step_and_units() {
+        let mut packet = sample_status().encode().unwrap();
+        packet[14] = 3;
+        packet[26] = 0;
+
+        let status = DeviceStatus::read_from(packet.as_slice()).unwrap();
+
+        assert_eq!(status.biorhythm_step(), 3);
+        assert_eq!(status.units(), Some(TemperatureUnit::Celsius));
+    }
+
+    #[test]
+    fn device_status_tolerates_unknown_units() {
+        let mut packet = sample_status().encode().unwrap();
+        packet[26] = 7;
+
+        let status = DeviceStatus::read_from(packet.as_slice()).unwrap();
+
+        assert_eq!(status.units(), None);
+        assert_eq!(status.encode().unwrap(), packet);
+    }
+
+    #[test]
+    fn device_status_rejects_short_packets() {
+        let packet = sample_status().encode().unwrap();
+
+        assert!(matches!(
+            DeviceStatus::read_from(&packet[..20]),
+            Err(InterfaceError::NotEnoughData)
+        ));
+    }
+
+    #[test]
+    fn command_round_trips() {
+        let commands = [
+            Command::Button(ButtonCode::Turbo),
+            Command::SetTime {
+                hours: 2,
+                minutes: 45,
+            },
+            Command::SetTemp(TempParam::Celsius(Temperature::from_half_degrees(63))),
+            Command::SetFan(FanParam::Step(19)),
+            Command::SetClock {
+                hours: 23,
+                minutes: 59,
+            },
+            Command::SetParam(SetParamKind::DeviceName(String::from("Upstairs"))),
+            Command::SetParam(SetParamKind::MemoryName {
+                slot: 2,
+                name: String::from("Warm Up"),
+            }),
+            Command::SetParam(SetParamKind::BiorhythmFragment {
+                slot: 3,
+                fragment: 4,
+                data: [7; biorhythm::FRAGMENT_LEN],
+            }),
+        ];
+
+        for command in commands {
+            let bytes = command.encode().unwrap();
+            assert_eq!(Command::read_from(bytes.as_slice()).unwrap(), command);
+        }
+    }
+
+    #[test]
+    fn parameters_round_trip() {
+        let version = |major, minor, patch| Version {
+            major,
+            minor,
+            patch,
+        };
+        let parameter = Parameter {
+            code: ParameterCode::FirmwareVersionCodes,
+            value: ParameterValue::FirmwareVersionCodes(vec![version(3, 1, 4), version(1, 0, 2)]),
+        };
+
+        let bytes = parameter.encode().unwrap();
+
+        assert_eq!(bytes.len(), PARAM_LEN + 2);
+        assert_eq!(Parameter::read_from(bytes.as_slice()).unwrap(), parameter);
+        assert!(matches!(
+            SetParamKind::read_from(bytes.as_slice()),
+            Err(InterfaceError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn command_decode_normalizes_units() {
+        // 75°F is 23.9°C, so the closest the device can get is 24°C
+        let fahrenheit = Command::SetTemp(TempParam::Fahrenheit(75)).encode().unwrap();
+        let percent = Command::SetFan(FanParam::Percent(50)).encode().unwrap();
+
+        assert_eq!(
+            Command::read_from(fahrenheit.as_slice()).unwrap(),
+            Command::SetTemp(TempParam::Celsius(Temperature::from_half_degrees(48)))
+        );
+        assert_eq!(
+            Command::read_from(percent.as_slice()).unwrap(),
+            Command::SetFan(FanParam::Step(9))
+        );
+    }
+
+    #[test]
+    fn command_decode_reports_unknown_codes() {
+        assert!(matches!(
+            Command::read_from([0x55u8].as_slice()),
+            Err(InterfaceError::UnknownCommandClass(0x55))
+        ));
+        assert!(matches!(
+            Command::read_from([CommandClass::Button as u8, 0x7f].as_slice()),
+            Err(InterfaceError::UnknownButtonCode(0x7f))
+        ));
+        assert!(matches!(
+            Command::read_from([CommandClass::SetTime as u8, 1].as_slice()),
+            Err(InterfaceError::NotEnoughData)
+        ));
+    }
+
+    #[test]
+    fn names_are_limited_by_bytes_not_characters() {
+        // 14 bytes, then a two byte character that would end past the limit
+        let name = "Schlafzimmer 1ü";
+        assert_eq!(name.chars().count(), 15);
+
+        assert!(matches!(
+            validate_name(name),
+            Err(InterfaceError::NameTooLong { len: 16, max: 15 })
+        ));
+        assert!(validate_name("Schlafzimmer 1").is_ok());
+        assert!(validate_name("Bed\0room").is_err());
+    }
+}
@@ -1,201 +1,1358 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use bedjet_control::device::BedJet;
-use bedjet_control::proto::{Decode, DeviceStatus, DeviceStatusEvent};
-use bedjet_control::{ Command, Encode};
-use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter, ValueNotification};
+use bedjet_control::adapters::{AdapterEvent, AdapterFallback, AdapterState, AdapterTracker};
+use bedjet_control::clock::{keep_clock_in_sync, ClockSync};
+use bedjet_control::device::{BedJet, TemperaturePolicy};
+use bedjet_control::discovery::{DiscoveredDevice, DiscoveryEvent, DiscoveryTracker};
+use bedjet_control::error::AppError;
+use bedjet_control::firmware::{FirmwareInfo, FirmwareUpdateOutcome, FirmwareUpdateProgressEvent};
+use bedjet_control::manager::{DeviceCommandResult, DeviceManager};
+use bedjet_control::proto::{DeviceSetting, DeviceStatusEvent};
+use bedjet_control::registry::{DeviceRegistry, KnownDevice};
+use bedjet_control::schedule::{
+    run_schedule, Schedule, ScheduleProgressEvent, ScheduleRun, ScheduleStore,
+};
+use bedjet_control::settings::Settings;
+use bedjet_control::simulator::{SimulatedBedJet, SimulatorTransport};
+use bedjet_control::store::{self, StoreError};
+use bedjet_control::supervisor::{
+    ConnectionState, ConnectionStateEvent, ReconnectPolicy, Supervisor,
+};
+use bedjet_control::sync::{sync_zones, ZoneSync};
+use bedjet_control::temperature::Temperature;
+use bedjet_control::thermostat::{run_thermostat, FileSource, TemperatureSource, Thermostat};
+use bedjet_control::transport::DynTransport;
+use bedjet_control::Command;
+use btleplug::api::{Central, CentralEvent, Manager as _, Peripheral as _, ScanFilter};
 use btleplug::platform::{Adapter, Manager, Peripheral};
-use futures::future::join_all;
-use futures::{Stream, StreamExt};
-use std::collections::HashMap;
-use std::pin::Pin;
+use chrono::Local;
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
-use tauri::async_runtime::Mutex;
+use std::time::{Duration, Instant};
+use tauri::async_runtime::{JoinHandle, Mutex};
 use tauri::{AppHandle, Manager as _, State};
+use tokio::sync::{mpsc, watch};
 use tokio::time;
 
-#[derive(Debug, Default)]
-struct BTAdapters(Arc<Mutex<HashMap<String, Adapter>>>);
+/// A BedJet behind whichever transport the app was started with
+type AppBedJet = BedJet<DynTransport>;
+
+#[derive(Default)]
+/// The Bluetooth adapters on the system, polled so adapters that are unplugged or powered off
+/// are noticed
+struct BTAdapters {
+    manager: Mutex<Option<Manager>>,
+    adapters: Mutex<HashMap<String, Adapter>>,
+    tracker: Mutex<AdapterTracker>,
+    fallback: Mutex<AdapterFallback>,
+}
 impl BTAdapters {
-    async fn get_adapter(&self, name: &str) -> Option<Adapter> {
-        let adapter_map = self.0.lock().await;
-        let adapter = adapter_map.get(name).cloned();
-        // If the name doesn't exist. Try and get an adapter that's in the map
-        if adapter.is_none() {
-            return adapter_map.values().next().cloned();
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    /// Lists the adapters again, returning what changed since the last time
+    async fn refresh(&self) -> Result<Vec<AdapterEvent>, AppError> {
+        let manager = {
+            let mut manager = self.manager.lock().await;
+            match manager.as_ref() {
+                Some(manager) => manager.clone(),
+                None => manager.insert(Manager::new().await?).clone(),
+            }
+        };
+
+        let mut adapter_map: HashMap<String, Adapter> = HashMap::new();
+        let mut states = Vec::new();
+        for adapter in manager.adapters().await? {
+            let Ok(info) = adapter.adapter_info().await else {
+                continue;
+            };
+            // A powered off adapter is still listed, but fails requests. btleplug 0.10 has no way
+            // to ask for the power state, so this is a guess, and an adapter failing for any other
+            // reason shows up as unavailable too
+            let state = match adapter.peripherals().await {
+                Ok(_) => AdapterState::Available,
+                Err(_) => AdapterState::Unavailable,
+            };
+            states.push((info.clone(), state));
+            adapter_map.insert(info, adapter);
         }
 
-        adapter
+        *self.adapters.lock().await = adapter_map;
+        Ok(self.tracker.lock().await.update(states))
+    }
+
+    async fn first_available(&self) -> Result<(String, Adapter), AppError> {
+        let name = self
+            .tracker
+            .lock()
+            .await
+            .first_available()
+            .ok_or(AppError::AdapterNotFound)?
+            .to_owned();
+        self.get_adapter(&name).await
+    }
+
+    /// The adapter to use when `name` is asked for, following the fallback policy
+    async fn get_adapter(&self, name: &str) -> Result<(String, Adapter), AppError> {
+        let fallback = *self.fallback.lock().await;
+        let tracker = self.tracker.lock().await;
+        let Some(resolved) = tracker.resolve(name, fallback) else {
+            return Err(match tracker.state(name) {
+                Some(AdapterState::Unavailable) => AppError::AdapterUnavailable(name.to_owned()),
+                _ => AppError::AdapterNotFound,
+            });
+        };
+        if resolved != name {
+            log::warn!("Adapter {name} can't be used, falling back to {resolved}");
+        }
+
+        self.adapters
+            .lock()
+            .await
+            .get(resolved)
+            .cloned()
+            .map(|adapter| (resolved.to_owned(), adapter))
+            .ok_or(AppError::AdapterNotFound)
     }
 }
 
-#[tauri::command]
-async fn get_btle_adapters(state: State<'_, BTAdapters>) -> Result<Vec<String>, ()> {
-    let manager = Manager::new().await.ok().unwrap();
-    let adapters = manager.adapters().await.ok().unwrap_or_default();
+/// Sends adapter changes to the frontend, and drops whatever was using an adapter that went away
+async fn handle_adapter_events(handle: &AppHandle, events: Vec<AdapterEvent>) {
+    for event in events {
+        let gone = match &event {
+            AdapterEvent::AdapterRemoved { name }
+            | AdapterEvent::AdapterStateChanged {
+                name,
+                state: AdapterState::Unavailable,
+            } => Some(name.clone()),
+            _ => None,
+        };
+        let _ = handle.emit_all("Adapter", event);
 
-    let mut adapter_map: HashMap<String, Adapter> = HashMap::new();
+        if let Some(name) = gone {
+            handle
+                .state::<BedJets>()
+                .disconnect_adapter(&name, handle)
+                .await;
+            handle.state::<Discovery>().stop_on(&name).await;
+        }
+    }
+}
 
-    for adapter in adapters {
-        let Ok(info) = adapter.adapter_info().await else {
-            continue;
+/// Polls the adapters for as long as the app runs
+async fn watch_adapters(handle: AppHandle) {
+    let mut poll = time::interval(BTAdapters::POLL_INTERVAL);
+    loop {
+        poll.tick().await;
+        match handle.state::<BTAdapters>().refresh().await {
+            Ok(events) => handle_adapter_events(&handle, events).await,
+            Err(e) => log::error!("Failed to list adapters: {e}"),
+        }
+    }
+}
+
+#[derive(Default)]
+/// Simulated BedJets that stand in for Bluetooth, by id. Set `BEDJET_SIMULATORS` to how many
+/// there should be to use them instead of the adapters on the system
+struct Simulators(HashMap<String, SimulatorTransport>);
+
+impl Simulators {
+    const ENV_VAR: &'static str = "BEDJET_SIMULATORS";
+    /// The adapter the simulated devices are found on
+    const ADAPTER: &'static str = "Simulator";
+    const TICK: Duration = Duration::from_secs(1);
+
+    fn from_env() -> Self {
+        let Ok(count) = std::env::var(Self::ENV_VAR) else {
+            return Self::default();
         };
-        adapter_map.insert(info, adapter);
+        let count: usize = count.parse().unwrap_or_else(|_| {
+            log::warn!(
+                "{} should be a number of devices, not {count}",
+                Self::ENV_VAR
+            );
+            0
+        });
+
+        Self(
+            (1..=count)
+                .map(|n| {
+                    let mut device = SimulatedBedJet::default();
+                    device.name = format!("BedJet Sim {n}");
+                    (format!("simulator-{n}"), SimulatorTransport::new(device))
+                })
+                .collect(),
+        )
+    }
+
+    fn enabled(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn bedjet(&self, id: &str) -> Option<Arc<AppBedJet>> {
+        let transport = self.0.get(id)?.clone();
+        Some(Arc::new(BedJet::new(Arc::new(transport) as DynTransport)))
+    }
+
+    /// Runs the simulated devices in real time for as long as the app runs
+    async fn run(handle: AppHandle) {
+        let mut tick = time::interval(Self::TICK);
+        loop {
+            tick.tick().await;
+            for transport in handle.state::<Simulators>().0.values() {
+                transport.advance(Self::TICK);
+            }
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_btle_adapters(
+    state: State<'_, BTAdapters>,
+    simulators: State<'_, Simulators>,
+    handle: AppHandle,
+) -> Result<Vec<String>, AppError> {
+    if simulators.enabled() {
+        return Ok(vec![Simulators::ADAPTER.to_owned()]);
     }
 
-    let response: Vec<String> = adapter_map.keys().cloned().collect();
+    let events = state.refresh().await?;
+    handle_adapter_events(&handle, events).await;
 
-    let mut state_adapters = state.0.lock().await;
-    *state_adapters = adapter_map;
+    Ok(state.tracker.lock().await.names())
+}
 
-    Ok(response)
+#[tauri::command]
+async fn set_adapter_fallback(
+    state: State<'_, BTAdapters>,
+    fallback: AdapterFallback,
+) -> Result<(), AppError> {
+    *state.fallback.lock().await = fallback;
+    Ok(())
 }
 
 #[derive(Debug, Default)]
-struct BTPeripherals(Arc<Mutex<HashMap<String, Peripheral>>>);
+/// BedJet peripherals found by discovery, with the name of the adapter each was found on
+struct BTPeripherals(Arc<Mutex<HashMap<String, (Peripheral, String)>>>);
 impl BTPeripherals {
-    async fn get_peripheral(&self, id: &str) -> Option<Peripheral> {
-        self.0.lock().await.get(id).cloned()
+    async fn get_peripheral(&self, id: &str) -> Result<(Peripheral, String), AppError> {
+        self.0
+            .lock()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::DeviceNotFound(id.to_owned()))
     }
 }
 
+struct Connection {
+    supervisor: Arc<Supervisor<DynTransport>>,
+    /// The adapter the device is connected through
+    adapter: String,
+    /// The supervisor, the tasks forwarding its state and the device status to the frontend, and
+    /// the one keeping the device's clock set
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Connection {
+    fn new(id: String, adapter: String, bedjet: Arc<AppBedJet>, handle: AppHandle) -> Self {
+        let status = tauri::async_runtime::spawn({
+            let mut status = bedjet.subscribe_status();
+            let (id, handle) = (id.clone(), handle.clone());
+            async move {
+                while status.changed().await.is_ok() {
+                    let Some(status) = *status.borrow_and_update() else {
+                        continue;
+                    };
+                    let _ = handle.emit_all(
+                        "DeviceStatus",
+                        DeviceStatusEvent {
+                            id: id.clone(),
+                            status,
+                        },
+                    );
+                }
+            }
+        });
+
+        let supervisor = Arc::new(Supervisor::new(bedjet, ReconnectPolicy::default()));
+        let clock = tauri::async_runtime::spawn({
+            let bedjet = supervisor.bedjet().clone();
+            let state = supervisor.subscribe_state();
+            let enabled = handle.state::<AppSettings>().sync_clock.subscribe();
+            async move {
+                keep_clock_in_sync(&bedjet, state, enabled, ClockSync::default()).await;
+            }
+        });
+        let states = tauri::async_runtime::spawn({
+            let mut states = supervisor.subscribe_state();
+            async move {
+                while states.changed().await.is_ok() {
+                    let state = *states.borrow_and_update();
+                    let _ = handle.emit_all(
+                        "ConnectionState",
+                        ConnectionStateEvent {
+                            id: id.clone(),
+                            state,
+                        },
+                    );
+                    // The supervisor gave up, so stop handing out a device that isn't coming
+                    // back. This stops the task too
+                    if state == ConnectionState::Failed {
+                        let _ = handle.state::<BedJets>().remove(&id).await;
+                    }
+                }
+            }
+        });
+        let run = tauri::async_runtime::spawn({
+            let supervisor = supervisor.clone();
+            async move {
+                supervisor.run().await;
+            }
+        });
+
+        Self {
+            supervisor,
+            adapter,
+            tasks: vec![status, states, run, clock],
+        }
+    }
+
+    fn stop(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Default)]
+/// The BedJets that are connected, by peripheral id
+struct BedJets {
+    /// The connected devices, which commands are sent through, and the groups of them
+    manager: DeviceManager<DynTransport>,
+    connections: Arc<Mutex<HashMap<String, Connection>>>,
+    /// Tells supervisors about disconnects reported by each adapter that's been scanned with,
+    /// by adapter name
+    disconnect_watchers: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl BedJets {
+    async fn get(&self, id: &str) -> Result<Arc<AppBedJet>, AppError> {
+        self.manager.get(id).ok_or(AppError::NotConnected)
+    }
+
+    /// Every change in a BedJet's connection state. Closes once it stops being supervised
+    async fn connection_state(
+        &self,
+        id: &str,
+    ) -> Result<watch::Receiver<ConnectionState>, AppError> {
+        self.connections
+            .lock()
+            .await
+            .get(id)
+            .map(|connection| connection.supervisor.subscribe_state())
+            .ok_or(AppError::NotConnected)
+    }
+
+    /// Starts supervising a BedJet, replacing any previous connection to it
+    async fn insert(&self, id: &str, adapter: String, bedjet: Arc<AppBedJet>, handle: AppHandle) {
+        self.manager.insert(id, bedjet.clone());
+        let connection = Connection::new(id.to_owned(), adapter, bedjet, handle);
+        if let Some(previous) = self
+            .connections
+            .lock()
+            .await
+            .insert(id.to_owned(), connection)
+        {
+            previous.stop();
+        }
+    }
+
+    async fn remove(&self, id: &str) -> Result<Arc<AppBedJet>, AppError> {
+        let connection = self
+            .connections
+            .lock()
+            .await
+            .remove(id)
+            .ok_or(AppError::NotConnected)?;
+        self.manager.remove(id);
+        let bedjet = connection.supervisor.bedjet().clone();
+        connection.stop();
+        Ok(bedjet)
+    }
+
+    /// Stops supervising every BedJet on an adapter that's gone, marking them disconnected
+    async fn disconnect_adapter(&self, adapter: &str, handle: &AppHandle) {
+        let mut connections = self.connections.lock().await;
+        let ids: Vec<String> = connections
+            .iter()
+            .filter(|(_, connection)| connection.adapter == adapter)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in ids {
+            if let Some(connection) = connections.remove(&id) {
+                connection.stop();
+            }
+            self.manager.remove(&id);
+            emit_disconnected(handle, id);
+        }
+        if let Some(watcher) = self.disconnect_watchers.lock().await.remove(adapter) {
+            watcher.abort();
+        }
+    }
+
+    /// Forwards disconnect events from an adapter to the supervisor of the device, replacing
+    /// any previous watcher for the same adapter
+    async fn watch_disconnects(&self, name: &str, adapter: &Adapter) -> Result<(), AppError> {
+        let mut events = adapter.events().await?;
+        let connections = self.connections.clone();
+        let watcher = tauri::async_runtime::spawn(async move {
+            while let Some(event) = events.next().await {
+                let CentralEvent::DeviceDisconnected(id) = event else {
+                    continue;
+                };
+                if let Some(connection) = connections.lock().await.get(&id.to_string()) {
+                    connection.supervisor.disconnected();
+                }
+            }
+        });
+
+        if let Some(previous) = self
+            .disconnect_watchers
+            .lock()
+            .await
+            .insert(name.to_owned(), watcher)
+        {
+            previous.abort();
+        }
+        Ok(())
+    }
+}
+
+fn emit_disconnected(handle: &AppHandle, id: String) {
+    let _ = handle.emit_all(
+        "ConnectionState",
+        ConnectionStateEvent {
+            id,
+            state: ConnectionState::Disconnected,
+        },
+    );
+}
+
+#[derive(Default)]
+/// Devices found by the running discovery, if there is one
+struct Discovery {
+    tracker: Arc<Mutex<DiscoveryTracker>>,
+    /// The adapter being scanned with, by name, and the task handling its events
+    scan: Mutex<Option<(String, Adapter, JoinHandle<()>)>>,
+}
+
+impl Discovery {
+    async fn stop(&self) -> Result<(), AppError> {
+        if let Some((_, adapter, task)) = self.scan.lock().await.take() {
+            task.abort();
+            adapter.stop_scan().await?;
+        }
+        Ok(())
+    }
+
+    async fn is_scanning_on(&self, name: &str) -> bool {
+        matches!(&*self.scan.lock().await, Some((scanning, _, _)) if scanning == name)
+    }
+
+    /// Stops discovery if it's scanning with the named adapter
+    async fn stop_on(&self, name: &str) {
+        let mut scan = self.scan.lock().await;
+        if scan
+            .as_ref()
+            .is_some_and(|(scanning, _, _)| scanning == name)
+        {
+            let (_, adapter, task) = scan.take().expect("checked above");
+            task.abort();
+            // The adapter is likely gone, so there may be no scan left to stop
+            let _ = adapter.stop_scan().await;
+        }
+    }
+}
+
+/// Turns an adapter event into discovery events, keeping track of the BedJet peripherals seen
+async fn handle_central_event(
+    (adapter_name, adapter): (&str, &Adapter),
+    tracker: &Mutex<DiscoveryTracker>,
+    peripherals: &Mutex<HashMap<String, (Peripheral, String)>>,
+    event: CentralEvent,
+) -> Vec<DiscoveryEvent> {
+    let id = match event {
+        CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id) => id,
+        _ => return Vec::new(),
+    };
+    let Ok(peripheral) = adapter.peripheral(&id).await else {
+        return Vec::new();
+    };
+    let Ok(Some(properties)) = peripheral.properties().await else {
+        return Vec::new();
+    };
+    // Not every platform applies the scan filter to events
+    if !properties.services.contains(&BedJet::SERVICE_UUID) {
+        return Vec::new();
+    }
+
+    let id = id.to_string();
+    peripherals
+        .lock()
+        .await
+        .insert(id.clone(), (peripheral, adapter_name.to_owned()));
+    tracker
+        .lock()
+        .await
+        .seen(&id, properties.local_name, properties.rssi, Instant::now())
+        .into_iter()
+        .collect()
+}
+
 #[tauri::command]
-async fn scan_bedjets(
+/// Scans for BedJets until stopped, sending `Discovery` events as they come and go
+async fn start_discovery(
     adapter_state: State<'_, BTAdapters>,
     peripheral_state: State<'_, BTPeripherals>,
+    bedjets: State<'_, BedJets>,
+    discovery: State<'_, Discovery>,
+    simulators: State<'_, Simulators>,
+    handle: AppHandle,
     adapter: String,
-) -> Result<Vec<String>, ()> {
-    let adapter = adapter_state.get_adapter(&adapter).await.unwrap();
+) -> Result<(), AppError> {
+    if simulators.enabled() {
+        discovery.stop().await?;
+        let mut tracker = discovery.tracker.lock().await;
+        *tracker = DiscoveryTracker::default();
+        for (id, transport) in &simulators.0 {
+            let name = transport.device().name.clone();
+            if let Some(event) = tracker.seen(id, Some(name), None, Instant::now()) {
+                let _ = handle.emit_all("Discovery", event);
+            }
+        }
+        return Ok(());
+    }
+
+    let (name, adapter) = adapter_state.get_adapter(&adapter).await?;
+    discovery.stop().await?;
+    *discovery.tracker.lock().await = DiscoveryTracker::default();
+
+    bedjets.watch_disconnects(&name, &adapter).await?;
+    let mut events = adapter.events().await?;
     adapter
         .start_scan(ScanFilter {
             services: vec![BedJet::SERVICE_UUID],
         })
-        .await
-        .unwrap();
+        .await?;
 
-    // Wait for discovery
-    time::sleep(Duration::from_secs(2)).await;
-    let peripherals = adapter.peripherals().await.unwrap();
-    join_all(peripherals.iter().map(|i| i.discover_services())).await;
-    let mut periph_map: HashMap<String, Peripheral> = HashMap::new();
-    for peripheral in peripherals.into_iter() {
-        periph_map.insert(peripheral.id().to_string(), peripheral);
-    }
+    let task = tauri::async_runtime::spawn({
+        let (name, adapter) = (name.clone(), adapter.clone());
+        let tracker = discovery.tracker.clone();
+        let peripherals = peripheral_state.0.clone();
+        let connections = bedjets.connections.clone();
+        async move {
+            let mut expiry = time::interval(Duration::from_secs(1));
+            loop {
+                let discovery_events = tokio::select! {
+                    event = events.next() => match event {
+                        Some(event) => {
+                            handle_central_event((&name, &adapter), &tracker, &peripherals, event)
+                                .await
+                        }
+                        None => break,
+                    },
+                    _ = expiry.tick() => {
+                        let connected: HashSet<String> =
+                            connections.lock().await.keys().cloned().collect();
+                        tracker.lock().await.expire(Instant::now(), &connected)
+                    }
+                };
+                for event in discovery_events {
+                    let _ = handle.emit_all("Discovery", event);
+                }
+            }
+        }
+    });
+    *discovery.scan.lock().await = Some((name, adapter, task));
 
-    let response = periph_map.keys().cloned().collect();
-    *peripheral_state.0.lock().await = periph_map;
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_discovery(discovery: State<'_, Discovery>) -> Result<(), AppError> {
+    discovery.stop().await
+}
 
-    Ok(response)
+#[tauri::command]
+async fn get_discovered_devices(
+    discovery: State<'_, Discovery>,
+) -> Result<Vec<DiscoveredDevice>, AppError> {
+    Ok(discovery.tracker.lock().await.devices())
 }
+
 #[tauri::command]
 async fn connect_bedjet(
     peripheral_state: State<'_, BTPeripherals>,
+    bedjets: State<'_, BedJets>,
+    simulators: State<'_, Simulators>,
     handle: AppHandle,
     bedjetid: String,
-) -> Result<(), ()> {
-    let id = peripheral_state.get_peripheral(&bedjetid).await.unwrap();
-    id.connect().await.unwrap();
-    id.discover_services().await.unwrap();
+) -> Result<(), AppError> {
+    if let Some(bedjet) = simulators.bedjet(&bedjetid) {
+        let adapter = Simulators::ADAPTER.to_owned();
+        bedjets.insert(&bedjetid, adapter, bedjet, handle).await;
+        return Ok(());
+    }
 
-    tauri::async_runtime::spawn(async move { handle_notify(id, handle).await });
+    let (peripheral, adapter) = peripheral_state.get_peripheral(&bedjetid).await?;
+
+    connect_peripheral(&bedjets, handle, &bedjetid, peripheral, adapter).await
+}
+
+async fn connect_peripheral(
+    bedjets: &BedJets,
+    handle: AppHandle,
+    id: &str,
+    peripheral: Peripheral,
+    adapter: String,
+) -> Result<(), AppError> {
+    peripheral.connect().await?;
+    peripheral.discover_services().await?;
+
+    let transport = BedJet::peripheral_transport(peripheral)?;
+    let bedjet = Arc::new(BedJet::new(Arc::new(transport) as DynTransport));
+    bedjets.insert(id, adapter, bedjet, handle).await;
 
     Ok(())
 }
 
+/// The devices the user has chosen to remember
+struct Registry(Mutex<DeviceRegistry>);
+
+/// How long auto-connect scans for devices before giving up on the ones it hasn't found
+const AUTO_CONNECT_SCAN: Duration = Duration::from_secs(30);
+
+/// Connects to the devices marked auto-connect, each through its preferred adapter, or the first
+/// available one if it doesn't have one
+async fn auto_connect(handle: AppHandle) {
+    let devices = handle.state::<Registry>().0.lock().await.auto_connect();
+    if devices.is_empty() {
+        return;
+    }
+
+    let simulators = handle.state::<Simulators>();
+    if simulators.enabled() {
+        let bedjets = handle.state::<BedJets>();
+        for device in devices {
+            if let Some(bedjet) = simulators.bedjet(&device.id) {
+                let adapter = Simulators::ADAPTER.to_owned();
+                bedjets
+                    .insert(&device.id, adapter, bedjet, handle.clone())
+                    .await;
+            }
+        }
+        return;
+    }
+
+    let adapters = handle.state::<BTAdapters>();
+    match adapters.refresh().await {
+        Ok(events) => handle_adapter_events(&handle, events).await,
+        Err(e) => log::error!("Failed to list adapters: {e}"),
+    }
+
+    let mut by_adapter: HashMap<String, (Adapter, HashSet<String>)> = HashMap::new();
+    for device in devices {
+        let adapter = match &device.preferred_adapter {
+            Some(name) => adapters.get_adapter(name).await,
+            None => adapters.first_available().await,
+        };
+        match adapter {
+            Ok((name, adapter)) => {
+                by_adapter
+                    .entry(name)
+                    .or_insert_with(|| (adapter, HashSet::new()))
+                    .1
+                    .insert(device.id);
+            }
+            Err(e) => log::warn!("Can't auto-connect to {}: {e}", device.nickname),
+        }
+    }
+
+    for (name, (adapter, ids)) in by_adapter {
+        if let Err(e) = auto_connect_on(&handle, &name, &adapter, ids).await {
+            log::warn!("Auto-connect on {name} failed: {e}");
+        }
+    }
+}
+
+/// Scans with an adapter until every device in `ids` is found and connected to, or the scan
+/// times out
+async fn auto_connect_on(
+    handle: &AppHandle,
+    name: &str,
+    adapter: &Adapter,
+    mut ids: HashSet<String>,
+) -> Result<(), AppError> {
+    let bedjets = handle.state::<BedJets>();
+    let peripherals = handle.state::<BTPeripherals>();
+    bedjets.watch_disconnects(name, adapter).await?;
+
+    let mut events = adapter.events().await?;
+    adapter
+        .start_scan(ScanFilter {
+            services: vec![BedJet::SERVICE_UUID],
+        })
+        .await?;
+
+    // Some platforms don't send discovery events for devices the adapter already knows about
+    let known = adapter.peripherals().await?.into_iter().map(|p| p.id());
+    let discovered = events.filter_map(|event| async move {
+        match event {
+            CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id) => Some(id),
+            _ => None,
+        }
+    });
+    let mut found = futures::stream::iter(known).chain(discovered).boxed();
+
+    let _ = time::timeout(AUTO_CONNECT_SCAN, async {
+        while let Some(peripheral_id) = found.next().await {
+            let id = peripheral_id.to_string();
+            if !ids.remove(&id) {
+                continue;
+            }
+            let Ok(peripheral) = adapter.peripheral(&peripheral_id).await else {
+                continue;
+            };
+            peripherals
+                .0
+                .lock()
+                .await
+                .insert(id.clone(), (peripheral.clone(), name.to_owned()));
+
+            let connected =
+                connect_peripheral(&bedjets, handle.clone(), &id, peripheral, name.to_owned())
+                    .await;
+            if let Err(e) = connected {
+                log::warn!("Failed to auto-connect to {id}: {e}");
+            }
+            if ids.is_empty() {
+                break;
+            }
+        }
+    })
+    .await;
+
+    // Leave the scan running if the user started discovery on this adapter meanwhile
+    if !handle.state::<Discovery>().is_scanning_on(name).await {
+        adapter.stop_scan().await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_known_devices(registry: State<'_, Registry>) -> Result<Vec<KnownDevice>, AppError> {
+    Ok(registry.0.lock().await.devices())
+}
+
+#[tauri::command]
+async fn add_known_device(
+    registry: State<'_, Registry>,
+    device: KnownDevice,
+) -> Result<(), AppError> {
+    Ok(registry.0.lock().await.add(device)?)
+}
+
+#[tauri::command]
+async fn update_known_device(
+    registry: State<'_, Registry>,
+    device: KnownDevice,
+) -> Result<(), AppError> {
+    Ok(registry.0.lock().await.update(device)?)
+}
+
+#[tauri::command]
+async fn forget_device(
+    bedjets: State<'_, BedJets>,
+    registry: State<'_, Registry>,
+    bedjetid: String,
+) -> Result<(), AppError> {
+    let mut registry = registry.0.lock().await;
+    registry.forget(&bedjetid)?;
+    for (group, ids) in registry.groups() {
+        bedjets.manager.set_group(&group, ids);
+    }
+    Ok(())
+}
+
 #[tauri::command]
 async fn disconnect_bedjet(
-    peripheral_state: State<'_, BTPeripherals>,
+    bedjets: State<'_, BedJets>,
+    handle: AppHandle,
     bedjetid: String,
-) -> Result<(), ()> {
-    let id = peripheral_state.get_peripheral(&bedjetid).await.unwrap();
+) -> Result<(), AppError> {
+    let bedjet = bedjets.remove(&bedjetid).await?;
 
-    id.disconnect().await.unwrap();
+    bedjet.transport().disconnect().await?;
+    emit_disconnected(&handle, bedjetid);
 
     Ok(())
 }
 
 #[tauri::command]
 async fn send_command(
-    peripheral_state: State<'_, BTPeripherals>,
+    bedjets: State<'_, BedJets>,
     bedjetid: String,
     command: Command,
-) -> Result<(), ()> {
-    println!("Got Command: {command:#?}");
-    let periph = peripheral_state.get_peripheral(&bedjetid).await.unwrap();
+) -> Result<(), AppError> {
+    log::debug!("Got Command: {command}");
+    let bedjet = bedjets.get(&bedjetid).await?;
 
-    let command_char = periph
-        .clone()
-        .characteristics()
-        .into_iter()
-        .find(|i| i.uuid == BedJet::COMMANDS_UUID)
-        .unwrap();
-
-    periph
-        .write(
-            &command_char,
-            &command.encode().unwrap(),
-            btleplug::api::WriteType::WithoutResponse,
-        )
+    Ok(bedjet.send_command(command).await?)
+}
+
+#[tauri::command]
+/// Turns one of a BedJet's settings on or off. The device doesn't report its settings back, so
+/// there's no command to read them
+async fn set_device_setting(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+    setting: DeviceSetting,
+    enabled: bool,
+) -> Result<(), AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    Ok(bedjet.set_setting(setting, enabled).await?)
+}
+
+#[tauri::command]
+/// Sends a command to every device in a group, reporting which ones it failed for
+async fn send_group_command(
+    bedjets: State<'_, BedJets>,
+    group: String,
+    command: Command,
+) -> Result<Vec<DeviceCommandResult>, AppError> {
+    log::debug!("Got Command for {group}: {command}");
+    bedjets
+        .manager
+        .send_group_command(&group, command)
         .await
-        .unwrap();
+        .ok_or(AppError::GroupNotFound(group))
+}
 
+#[tauri::command]
+async fn get_groups(
+    bedjets: State<'_, BedJets>,
+) -> Result<BTreeMap<String, Vec<String>>, AppError> {
+    Ok(bedjets.manager.groups())
+}
+
+#[tauri::command]
+/// Creates or replaces a group of devices
+async fn set_group(
+    bedjets: State<'_, BedJets>,
+    registry: State<'_, Registry>,
+    group: String,
+    bedjetids: Vec<String>,
+) -> Result<(), AppError> {
+    registry
+        .0
+        .lock()
+        .await
+        .set_group(&group, bedjetids.clone())?;
+    bedjets.manager.set_group(&group, bedjetids);
     Ok(())
 }
 
-type _NotificationStream = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+#[tauri::command]
+async fn remove_group(
+    bedjets: State<'_, BedJets>,
+    registry: State<'_, Registry>,
+    group: String,
+) -> Result<(), AppError> {
+    registry.0.lock().await.remove_group(&group)?;
+    bedjets
+        .manager
+        .remove_group(&group)
+        .map(|_| ())
+        .ok_or(AppError::GroupNotFound(group))
+}
 
-async fn handle_notify(bedjet: Peripheral, handle: AppHandle) {
-    let status_char = bedjet
-        .characteristics()
-        .iter()
-        .find(|c| c.uuid == BedJet::DEVICE_STATUS_UUID)
-        .cloned()
-        .unwrap();
-    println!("Found Status: {status_char:#?}");
+#[derive(Default)]
+/// Tasks running for devices, each numbered so a replaced task can tell it's been replaced
+struct TaskMap {
+    started: u64,
+    tasks: HashMap<String, (u64, JoinHandle<()>)>,
+}
 
-    bedjet
-        .subscribe(&status_char)
-        .await
-        .expect("Failed to subscribe to status");
+#[derive(Clone, Default)]
+/// One background task per device id, each removing itself from the map once it ends
+struct DeviceTasks(Arc<Mutex<TaskMap>>);
 
-    let mut msg_stream = bedjet.notifications().await.unwrap();
+impl DeviceTasks {
+    /// Runs `task` for `id`, stopping the one already running for it
+    async fn start(
+        &self,
+        id: String,
+        task: impl std::future::Future<Output = ()> + Send + 'static,
+    ) {
+        let mut map = self.0.lock().await;
+        map.started += 1;
+        let number = map.started;
 
-    while let Some(mut msg) = msg_stream.next().await {
-        
-        if msg.value[0] != 0 {
-            if let Ok(mut msg_continued) = bedjet.read(&status_char).await {
-                msg.value.append(&mut msg_continued);
+        let tasks = self.clone();
+        let task_id = id.clone();
+        let handle = tauri::async_runtime::spawn(async move {
+            task.await;
+            let mut map = tasks.0.lock().await;
+            let replaced = map.tasks.get(&task_id).map(|(started, _)| *started) != Some(number);
+            if !replaced {
+                map.tasks.remove(&task_id);
             }
+        });
+        if let Some((_, previous)) = map.tasks.insert(id, (number, handle)) {
+            previous.abort();
         }
-        let data = &msg.value[1..];
+    }
 
-        if let Some(status) = DeviceStatus::decode(data) {
-            println!("{status:#?}");
-            handle
-                .emit_all(
-                    "DeviceStatus",
-                    DeviceStatusEvent {
-                        id: bedjet.id().to_string(),
-                        status,
-                    },
-                )
-                .unwrap();
-        };
+    async fn stop(&self, id: &str) {
+        if let Some((_, task)) = self.0.lock().await.tasks.remove(id) {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Default)]
+/// Running dual-zone syncs, by leader id
+struct ZoneSyncs(DeviceTasks);
+
+#[tauri::command]
+/// Keeps a follower in the leader's mode and timer, with its target temperature `offset` degrees
+/// celsius from the leader's
+async fn start_zone_sync(
+    bedjets: State<'_, BedJets>,
+    syncs: State<'_, ZoneSyncs>,
+    leader: String,
+    follower: String,
+    offset: f32,
+) -> Result<(), AppError> {
+    let leader_bedjet = bedjets.get(&leader).await?;
+    let follower_bedjet = bedjets.get(&follower).await?;
+    let leader_state = bedjets.connection_state(&leader).await?;
+    let follower_state = bedjets.connection_state(&follower).await?;
+
+    let mut sync = ZoneSync::new((offset * 2.0).round() as i16);
+    // Ends by itself once either device is disconnected for good
+    syncs
+        .0
+        .start(leader, async move {
+            sync_zones(
+                &leader_bedjet,
+                &follower_bedjet,
+                leader_state,
+                follower_state,
+                &mut sync,
+            )
+            .await;
+        })
+        .await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_zone_sync(syncs: State<'_, ZoneSyncs>, leader: String) -> Result<(), AppError> {
+    syncs.0.stop(&leader).await;
+    Ok(())
+}
+
+#[derive(Default)]
+/// Running thermostats, by device id
+struct Thermostats(DeviceTasks);
+
+#[tauri::command]
+/// Holds the temperature read from the file at `path` at `setpoint` while the device is heating,
+/// by adjusting its target temperature and fan speed
+async fn start_thermostat(
+    bedjets: State<'_, BedJets>,
+    thermostats: State<'_, Thermostats>,
+    bedjetid: String,
+    setpoint: Temperature,
+    path: String,
+) -> Result<(), AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+    let state = bedjets.connection_state(&bedjetid).await?;
+    let source = FileSource::new(path);
+    // Fails early if the sensor can't be read at all
+    source.read().await?;
+
+    // Ends by itself once the device is disconnected for good
+    thermostats
+        .0
+        .start(bedjetid, async move {
+            run_thermostat(&bedjet, state, &source, &mut Thermostat::new(setpoint)).await;
+        })
+        .await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_thermostat(
+    thermostats: State<'_, Thermostats>,
+    bedjetid: String,
+) -> Result<(), AppError> {
+    thermostats.0.stop(&bedjetid).await;
+    Ok(())
+}
+
+/// Schedules running on devices, and the tasks running them
+struct Schedules {
+    store: Arc<Mutex<ScheduleStore>>,
+    tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl Schedules {
+    /// Runs `f` on the store from a blocking thread, since changing it writes the file. The lock
+    /// is held until `f` returns, so changes are made in the order they were asked for
+    async fn with_store<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut ScheduleStore) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let mut store = self.store.clone().lock_owned().await;
+        tauri::async_runtime::spawn_blocking(move || f(&mut store))
+            .await
+            .expect("schedule store task panicked")
+    }
+}
+
+/// How often a schedule checks whether its device is back
+const SCHEDULE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Runs a schedule on a device whenever it's connected, until every step has been sent
+fn spawn_schedule(handle: AppHandle, mut run: ScheduleRun) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Ok(bedjet) = handle.state::<BedJets>().get(&run.device_id).await {
+                // Steps are reported synchronously, so their progress is saved alongside
+                let (progress, mut saves) = mpsc::unbounded_channel();
+                let emitter = &handle;
+                let running = run_schedule(&bedjet, &mut run, move |run, error| {
+                    let _ = emitter.emit_all(
+                        "ScheduleProgress",
+                        ScheduleProgressEvent {
+                            error: error.map(|error| error.to_string()),
+                            ..run.progress(Local::now().naive_local())
+                        },
+                    );
+                    let _ = progress.send(run.clone());
+                });
+                let saving = async {
+                    while let Some(run) = saves.recv().await {
+                        let saved = handle
+                            .state::<Schedules>()
+                            .with_store(move |store| store.insert(run))
+                            .await;
+                        if let Err(e) = saved {
+                            log::error!("Failed to save schedule progress: {e}");
+                        }
+                    }
+                };
+                let (result, ()) = tokio::join!(running, saving);
+                if result.is_ok() {
+                    break;
+                }
+            }
+            time::sleep(SCHEDULE_RETRY_INTERVAL).await;
+        }
+
+        let removed = handle
+            .state::<Schedules>()
+            .with_store(move |store| store.remove(&run.device_id))
+            .await;
+        if let Err(e) = removed {
+            log::error!("Failed to remove finished schedule: {e}");
+        }
+    })
+}
+
+#[tauri::command]
+/// Starts a schedule on a device, replacing the one running on it. It runs until its last step,
+/// even if the frontend reloads or the app restarts
+async fn start_schedule(
+    schedules: State<'_, Schedules>,
+    handle: AppHandle,
+    bedjetid: String,
+    schedule: Schedule,
+) -> Result<(), AppError> {
+    schedule.validate()?;
+
+    let run = ScheduleRun::new(bedjetid.clone(), schedule, Local::now().naive_local());
+    let mut tasks = schedules.tasks.lock().await;
+    if let Some(previous) = tasks.remove(&bedjetid) {
+        previous.abort();
+    }
+    let saved = run.clone();
+    schedules
+        .with_store(move |store| store.insert(saved))
+        .await?;
+    tasks.insert(bedjetid, spawn_schedule(handle, run));
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_schedule(schedules: State<'_, Schedules>, bedjetid: String) -> Result<(), AppError> {
+    if let Some(task) = schedules.tasks.lock().await.remove(&bedjetid) {
+        task.abort();
+    }
+    schedules
+        .with_store(move |store| store.remove(&bedjetid))
+        .await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_schedules(schedules: State<'_, Schedules>) -> Result<Vec<ScheduleRun>, AppError> {
+    Ok(schedules.with_store(|store| store.runs()).await)
+}
+
+/// The app-wide settings, and where they're saved
+struct AppSettings {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+    /// Tells each connection's clock task whether to keep the device's clock set
+    sync_clock: watch::Sender<bool>,
+}
+
+#[tauri::command]
+async fn get_settings(settings: State<'_, AppSettings>) -> Result<Settings, AppError> {
+    Ok(*settings.settings.lock().await)
+}
+
+#[tauri::command]
+async fn set_settings(
+    app_settings: State<'_, AppSettings>,
+    settings: Settings,
+) -> Result<(), AppError> {
+    let mut current = app_settings.settings.lock().await;
+    store::save(&app_settings.path, &settings)?;
+    *current = settings;
+    app_settings.sync_clock.send_replace(settings.sync_clock);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_memory_names(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+) -> Result<Vec<String>, AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    Ok(bedjet.read_memory_names().await?)
+}
+
+#[tauri::command]
+async fn set_memory_name(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+    slot: u8,
+    name: String,
+) -> Result<(), AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    Ok(bedjet.set_memory_name(slot, name).await?)
+}
+
+#[tauri::command]
+/// Sets what happens to temperatures outside the range the device's current mode allows
+async fn set_temperature_policy(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+    policy: TemperaturePolicy,
+) -> Result<(), AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+    bedjet.set_temperature_policy(policy);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_bedjet_name(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+) -> Result<String, AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    Ok(bedjet.get_friendly_name().await?)
+}
+
+#[tauri::command]
+async fn rename_bedjet(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+    name: String,
+) -> Result<(), AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    Ok(bedjet.rename(&name).await?)
+}
+
+#[tauri::command]
+async fn get_firmware(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+) -> Result<FirmwareInfo, AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    Ok(bedjet.discover_firmware().await?)
+}
+
+#[tauri::command]
+async fn provision_wifi(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+    ssid: String,
+    password: String,
+) -> Result<(), AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    Ok(bedjet.provision_wifi(&ssid, &password).await?)
+}
+
+#[tauri::command]
+async fn update_firmware(
+    bedjets: State<'_, BedJets>,
+    bedjetid: String,
+    handle: AppHandle,
+) -> Result<FirmwareUpdateOutcome, AppError> {
+    let bedjet = bedjets.get(&bedjetid).await?;
+
+    // The device restarts to finish an update, and its supervisor reconnects to it
+    let outcome = bedjet
+        .update_firmware(|event| {
+            let _ = handle.emit_all(
+                "FirmwareUpdate",
+                FirmwareUpdateProgressEvent {
+                    id: bedjetid.clone(),
+                    event,
+                },
+            );
+        })
+        .await?;
+
+    Ok(outcome)
+}
+
+/// Loads a file saved by the app. A corrupt one is logged and set aside, and `load` runs again
+/// to start over, so a bad file doesn't stop the app from starting. Failing to read it at all
+/// is still an error, since the file may be fine
+fn load_or_start_over<T>(
+    path: &Path,
+    load: impl Fn(&Path) -> Result<T, StoreError>,
+) -> Result<T, StoreError> {
+    match load(path) {
+        Err(e @ StoreError::Corrupt { .. }) => {
+            log::error!("{e}");
+            let aside = store::set_aside(path)?;
+            log::warn!("Moved it to {} and started over", aside.display());
+            load(path)
+        }
+        result => result,
     }
 }
 
 fn main() {
+    env_logger::init();
+
     tauri::Builder::default()
         .manage(BTAdapters::default())
         .manage(BTPeripherals::default())
+        .manage(BedJets::default())
+        .manage(Discovery::default())
+        .manage(ZoneSyncs::default())
+        .manage(Thermostats::default())
+        .manage(Simulators::from_env())
+        .setup(|app| {
+            let data_dir = app
+                .path_resolver()
+                .app_data_dir()
+                .ok_or("No app data directory to keep devices and schedules in")?;
+            let settings_path = data_dir.join(Settings::FILE_NAME);
+            let settings: Settings =
+                load_or_start_over(&settings_path, store::load::<Settings>)?.unwrap_or_default();
+            app.manage(AppSettings {
+                path: settings_path,
+                settings: Mutex::new(settings),
+                sync_clock: watch::channel(settings.sync_clock).0,
+            });
+
+            let registry = load_or_start_over(&data_dir.join(DeviceRegistry::FILE_NAME), |path| {
+                DeviceRegistry::load(path)
+            })?;
+            for (group, ids) in registry.groups() {
+                app.state::<BedJets>().manager.set_group(&group, ids);
+            }
+            app.manage(Registry(Mutex::new(registry)));
+
+            let schedules = load_or_start_over(&data_dir.join(ScheduleStore::FILE_NAME), |path| {
+                ScheduleStore::load(path)
+            })?;
+            let tasks = schedules
+                .runs()
+                .into_iter()
+                .map(|run| (run.device_id.clone(), spawn_schedule(app.handle(), run)))
+                .collect();
+            app.manage(Schedules {
+                store: Arc::new(Mutex::new(schedules)),
+                tasks: Mutex::new(tasks),
+            });
+
+            tauri::async_runtime::spawn(auto_connect(app.handle()));
+            if app.state::<Simulators>().enabled() {
+                log::info!("Using simulated BedJets instead of Bluetooth");
+                tauri::async_runtime::spawn(Simulators::run(app.handle()));
+            } else {
+                tauri::async_runtime::spawn(watch_adapters(app.handle()));
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             get_btle_adapters,
-            scan_bedjets,
+            set_adapter_fallback,
+            start_discovery,
+            stop_discovery,
+            get_discovered_devices,
             connect_bedjet,
             disconnect_bedjet,
-            send_command
+            send_command,
+            set_device_setting,
+            send_group_command,
+            get_groups,
+            set_group,
+            remove_group,
+            start_zone_sync,
+            stop_zone_sync,
+            start_thermostat,
+            stop_thermostat,
+            start_schedule,
+            stop_schedule,
+            get_schedules,
+            get_memory_names,
+            set_memory_name,
+            get_firmware,
+            provision_wifi,
+            update_firmware,
+            get_bedjet_name,
+            rename_bedjet,
+            set_temperature_policy,
+            get_known_devices,
+            add_known_device,
+            update_known_device,
+            forget_device,
+            get_settings,
+            set_settings
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,170 @@
+use crate::{device::BedJet, error::AppError, transport::BedJetTransport, Command};
+use futures::future::join_all;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+};
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+/// How a command sent to a group went for one of its devices
+pub struct DeviceCommandResult {
+    pub id: String,
+    /// Why the command failed, or `None` if it was sent
+    pub error: Option<AppError>,
+}
+
+#[derive(Debug)]
+/// The connected BedJets by id, and named groups of them that commands can be sent to together,
+/// like both sides of a bed.
+///
+/// Groups are kept by device id, so they can include devices that aren't connected right now.
+pub struct DeviceManager<T: BedJetTransport> {
+    devices: RwLock<HashMap<String, Arc<BedJet<T>>>>,
+    groups: RwLock<BTreeMap<String, Vec<String>>>,
+}
+
+impl<T: BedJetTransport> Default for DeviceManager<T> {
+    fn default() -> Self {
+        Self {
+            devices: RwLock::new(HashMap::new()),
+            groups: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<T: BedJetTransport> DeviceManager<T> {
+    /// Adds a device, returning the one it replaces
+    pub fn insert(&self, id: &str, bedjet: Arc<BedJet<T>>) -> Option<Arc<BedJet<T>>> {
+        self.devices.write().unwrap().insert(id.to_owned(), bedjet)
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Arc<BedJet<T>>> {
+        self.devices.write().unwrap().remove(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<BedJet<T>>> {
+        self.devices.read().unwrap().get(id).cloned()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.devices.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Creates or replaces a group
+    pub fn set_group(&self, name: &str, ids: Vec<String>) {
+        self.groups.write().unwrap().insert(name.to_owned(), ids);
+    }
+
+    pub fn remove_group(&self, name: &str) -> Option<Vec<String>> {
+        self.groups.write().unwrap().remove(name)
+    }
+
+    /// Every group, by name
+    pub fn groups(&self) -> BTreeMap<String, Vec<String>> {
+        self.groups.read().unwrap().clone()
+    }
+
+    /// Sends a command to every device in a group at the same time, returning how it went for
+    /// each of them in the group's order. Returns `None` if there's no group by that name
+    pub async fn send_group_command(
+        &self,
+        group: &str,
+        command: Command,
+    ) -> Option<Vec<DeviceCommandResult>> {
+        let ids = self.groups.read().unwrap().get(group)?.clone();
+        Some(self.send_command_to(&ids, command).await)
+    }
+
+    /// Sends a command to several devices at the same time, returning how it went for each
+    pub async fn send_command_to(
+        &self,
+        ids: &[String],
+        command: Command,
+    ) -> Vec<DeviceCommandResult> {
+        // Taken up front, so the lock isn't held while the commands are sent
+        let devices: Vec<_> = ids.iter().map(|id| (id.clone(), self.get(id))).collect();
+
+        join_all(devices.into_iter().map(|(id, bedjet)| {
+            let command = command.clone();
+            async move {
+                let result = match bedjet {
+                    Some(bedjet) => bedjet.send_command(command).await.map_err(AppError::from),
+                    None => Err(AppError::NotConnected),
+                };
+                DeviceCommandResult {
+                    id,
+                    error: result.err(),
+                }
+            }
+        }))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{proto::ButtonCode, transport::MemoryTransport, Encode};
+
+    #[tokio::test]
+    async fn group_commands_report_each_device() {
+        let manager = DeviceManager::default();
+        let left = MemoryTransport::with_bedjet_characteristics();
+        let right = MemoryTransport::with_bedjet_characteristics();
+        right.drop_connection();
+        manager.insert("left", Arc::new(BedJet::new(left.clone())));
+        manager.insert("right", Arc::new(BedJet::new(right.clone())));
+        manager.set_group(
+            "Master bedroom",
+            vec![
+                String::from("left"),
+                String::from("right"),
+                String::from("guest"),
+            ],
+        );
+
+        let command = Command::Button(ButtonCode::Cool);
+        let results = manager
+            .send_group_command("Master bedroom", command.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                DeviceCommandResult {
+                    id: String::from("left"),
+                    error: None
+                },
+                DeviceCommandResult {
+                    id: String::from("right"),
+                    error: Some(AppError::NotConnected)
+                },
+                DeviceCommandResult {
+                    id: String::from("guest"),
+                    error: Some(AppError::NotConnected)
+                },
+            ]
+        );
+        assert_eq!(
+            left.writes(),
+            vec![(BedJet::COMMANDS_UUID, command.encode().unwrap())]
+        );
+        assert!(right.writes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_groups_are_reported() {
+        let manager = DeviceManager::<MemoryTransport>::default();
+        manager.set_group("Bedroom", vec![String::from("left")]);
+        manager.remove_group("Bedroom");
+
+        assert!(manager
+            .send_group_command("Bedroom", Command::Button(ButtonCode::Stop))
+            .await
+            .is_none());
+    }
+}
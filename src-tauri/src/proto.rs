@@ -1,10 +1,14 @@
+use crate::temperature::Temperature;
 use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive as _;
 use serde::{Deserialize, Serialize};
 use typeshare::typeshare;
 
 #[typeshare]
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive, Serialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive, Serialize, Deserialize,
+)]
 pub enum OperatingMode {
     Standby = 0,
     NormalHeat = 1,
@@ -16,34 +20,85 @@ pub enum OperatingMode {
 }
 
 #[typeshare]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub struct DeviceStatus {
     /// The total runtime left on the device
     pub remaining_hours: u8,
     pub remaining_minutes: u8,
     pub remaining_seconds: u8,
-    /// Stored in units of 0.5 degrees celsius
-    pub actual_temp: u8,
-    /// Stored in units of 0.5 degrees celsius
-    pub target_temp: u8,
+    pub actual_temp: Temperature,
+    pub target_temp: Temperature,
     pub operating_mode: OperatingMode,
     /// Represented as a number between 0-19
     pub fan_step: u8,
     /// Maximum runtime for the current mode
     pub max_duration_hours: u8,
     pub max_duration_minutes: u8,
-    /// Stored in units of 0.5 degrees celsius
-    pub min_target_temp: u8,
-    /// Stored in units of 0.5 degrees celsius
-    pub max_target_temp: u8,
-    /// Stored in units of 0.5 degrees celsius
-    pub ambient_temp: u8,
+    pub min_target_temp: Temperature,
+    pub max_target_temp: Temperature,
+    pub ambient_temp: Temperature,
     pub shutdown_code: ShutDownCode,
     pub update_status: UpdateStatus,
+    /// Bytes 14, 15, 18 through 24 and 26 of the packet, which haven't been confirmed against a
+    /// real device. Kept so the packet can be written back out unchanged
+    pub unknown: [u8; 10],
+}
+
+#[typeshare]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// A device setting that's turned on and off with a pair of buttons.
+///
+/// The status packet doesn't report these in any byte that's been confirmed against a real
+/// device, so they're only ever set, not read back
+pub enum DeviceSetting {
+    LowPower,
+    BeeperMuted,
+    RingOfLight,
+}
+
+impl DeviceSetting {
+    /// The button that turns the setting on or off
+    pub fn button(self, enabled: bool) -> ButtonCode {
+        match (self, enabled) {
+            (Self::LowPower, true) => ButtonCode::SetLowPowerMode,
+            (Self::LowPower, false) => ButtonCode::SetNormalPowerMode,
+            (Self::BeeperMuted, true) => ButtonCode::MuteBeeper,
+            (Self::BeeperMuted, false) => ButtonCode::UnmuteBeeper,
+            (Self::RingOfLight, true) => ButtonCode::EnableRingOfLight,
+            (Self::RingOfLight, false) => ButtonCode::DisableRingOfLight,
+        }
+    }
 }
 
+impl OperatingMode {
+    /// The button that switches the device into this mode.
+    ///
+    /// Returns `None` for [`OperatingMode::Wait`], which the device only enters by itself
+    pub fn button(&self) -> Option<ButtonCode> {
+        let button = match self {
+            Self::Standby => ButtonCode::Stop,
+            Self::NormalHeat => ButtonCode::Heat,
+            Self::TurboHeat => ButtonCode::Turbo,
+            Self::ExtendedHeat => ButtonCode::ExternalHeat,
+            Self::Cool => ButtonCode::Cool,
+            Self::Dry => ButtonCode::Dry,
+            Self::Wait => return None,
+        };
+        Some(button)
+    }
+}
 
-#[derive(Debug, Clone, PartialEq)]
+#[typeshare]
+#[repr(u8)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive, Serialize, Deserialize,
+)]
+pub enum TemperatureUnit {
+    Celsius = 0,
+    Fahrenheit = 1,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[typeshare]
 pub struct DeviceStatusEvent {
     pub id: String,
@@ -88,6 +143,37 @@ pub enum UpdateStatus {
     UpdateFailed = 29,
 }
 
+impl UpdateStatus {
+    /// The device is partway through a connection test or firmware update
+    pub fn is_in_progress(&self) -> bool {
+        matches!(
+            self,
+            Self::Starting
+                | Self::ConnectingToAP
+                | Self::GotIPAddress
+                | Self::CheckingConnection
+                | Self::CheckingForUpdate
+                | Self::Updating
+                | Self::RestartingBedJet
+        )
+    }
+
+    /// A connection test or firmware update stopped because something went wrong
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::NoWiFiConfig
+                | Self::UnableToConnect
+                | Self::DHCPFailure
+                | Self::UnableToContactServer
+                | Self::ConnectionTestFailed
+                | Self::RadioDisabled
+                | Self::RestartingBedJetTerminal
+                | Self::UpdateFailed
+        )
+    }
+}
+
 #[typeshare]
 #[repr(u8)]
 #[derive(
@@ -126,8 +212,11 @@ pub enum ButtonCode {
     SetConfigCompleteFlag = 0x4f,
 }
 
+#[typeshare]
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive, Serialize, Deserialize,
+)]
 pub enum ParameterCode {
     DeviceName = 0x00,
     MemoryName1 = 0x01,
@@ -151,6 +240,62 @@ pub enum ParameterCode {
     FirmwareVersionCodes = 0x20,
 }
 
+impl ParameterCode {
+    /// The name parameter for memory slot 1 to 3
+    pub fn memory_name(slot: u8) -> Option<Self> {
+        if !(1..=3).contains(&slot) {
+            return None;
+        }
+        Self::from_u8(Self::MemoryName1 as u8 + slot - 1)
+    }
+
+    /// The memory slot this parameter names
+    pub fn memory_slot(&self) -> Option<u8> {
+        match self {
+            Self::MemoryName1 | Self::MemoryName2 | Self::MemoryName3 => {
+                Some(*self as u8 - Self::MemoryName1 as u8 + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// The name parameter for biorhythm slot 1 to 3
+    pub fn biorhythm_name(slot: u8) -> Option<Self> {
+        if !(1..=3).contains(&slot) {
+            return None;
+        }
+        Self::from_u8(Self::BiorhythmName1 as u8 + slot - 1)
+    }
+
+    /// Fragment 1 to 4 of the program in biorhythm slot 1 to 3
+    pub fn biorhythm_fragment(slot: u8, fragment: u8) -> Option<Self> {
+        if !(1..=3).contains(&slot) || !(1..=4).contains(&fragment) {
+            return None;
+        }
+        Self::from_u8(Self::Biorhythm1Fragment1 as u8 + (slot - 1) * 4 + fragment - 1)
+    }
+
+    /// The biorhythm slot this parameter belongs to,
+    /// and which fragment it is if it isn't the name
+    pub fn biorhythm_slot(&self) -> Option<(u8, Option<u8>)> {
+        let code = *self as u8;
+        match self {
+            Self::BiorhythmName1 | Self::BiorhythmName2 | Self::BiorhythmName3 => {
+                Some((code - Self::BiorhythmName1 as u8 + 1, None))
+            }
+            Self::DeviceName
+            | Self::MemoryName1
+            | Self::MemoryName2
+            | Self::MemoryName3
+            | Self::FirmwareVersionCodes => None,
+            _ => {
+                let offset = code - Self::Biorhythm1Fragment1 as u8;
+                Some((offset / 4 + 1, Some(offset % 4 + 1)))
+            }
+        }
+    }
+}
+
 #[typeshare]
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
@@ -161,4 +306,5 @@ pub enum CommandClass {
     SetFan = 0x07,
     SetClock = 0x08,
     SetParameter = 0x40,
+    GetParameter = 0x41,
 }
@@ -0,0 +1,253 @@
+use crate::{
+    proto::TemperatureUnit,
+    store::{self, StoreError},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+use thiserror::Error;
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// A BedJet the user has chosen to remember
+pub struct KnownDevice {
+    /// The peripheral id, which is the address on platforms that expose it
+    pub id: String,
+    pub nickname: String,
+    pub room: Option<String>,
+    /// The adapter to connect through, if it shouldn't be whichever is selected
+    pub preferred_adapter: Option<String>,
+    /// The units to show this device's temperatures in
+    pub preferred_units: Option<TemperatureUnit>,
+    /// Connect in the background when the app starts
+    #[serde(default)]
+    pub auto_connect: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("{0} is already a known device")]
+    AlreadyKnown(String),
+    #[error("{0} isn't a known device")]
+    Unknown(String),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    devices: Vec<KnownDevice>,
+    /// Groups of device ids, by name
+    #[serde(default)]
+    groups: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug)]
+/// The known devices and groups of them, saved as JSON to a file after every change
+pub struct DeviceRegistry {
+    path: PathBuf,
+    devices: BTreeMap<String, KnownDevice>,
+    groups: BTreeMap<String, Vec<String>>,
+}
+
+impl DeviceRegistry {
+    pub const FILE_NAME: &'static str = "devices.json";
+
+    /// Loads the registry saved at `path`, or starts an empty one if there isn't a file yet
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let file: RegistryFile = store::load(&path)?.unwrap_or_default();
+
+        let devices = file
+            .devices
+            .into_iter()
+            .map(|device| (device.id.clone(), device))
+            .collect();
+        Ok(Self {
+            path,
+            devices,
+            groups: file.groups,
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&KnownDevice> {
+        self.devices.get(id)
+    }
+
+    /// Every known device, ordered by id
+    pub fn devices(&self) -> Vec<KnownDevice> {
+        self.devices.values().cloned().collect()
+    }
+
+    /// The devices to connect to when the app starts
+    pub fn auto_connect(&self) -> Vec<KnownDevice> {
+        self.devices
+            .values()
+            .filter(|device| device.auto_connect)
+            .cloned()
+            .collect()
+    }
+
+    pub fn add(&mut self, device: KnownDevice) -> Result<(), RegistryError> {
+        if self.devices.contains_key(&device.id) {
+            return Err(RegistryError::AlreadyKnown(device.id));
+        }
+        self.devices.insert(device.id.clone(), device);
+        self.save()
+    }
+
+    /// Replaces the details of a known device
+    pub fn update(&mut self, device: KnownDevice) -> Result<(), RegistryError> {
+        let Some(known) = self.devices.get_mut(&device.id) else {
+            return Err(RegistryError::Unknown(device.id));
+        };
+        *known = device;
+        self.save()
+    }
+
+    /// Forgets a device, taking it out of every group it was in
+    pub fn forget(&mut self, id: &str) -> Result<KnownDevice, RegistryError> {
+        let device = self
+            .devices
+            .remove(id)
+            .ok_or_else(|| RegistryError::Unknown(id.to_owned()))?;
+        for ids in self.groups.values_mut() {
+            ids.retain(|member| member != id);
+        }
+        self.save()?;
+        Ok(device)
+    }
+
+    pub fn groups(&self) -> BTreeMap<String, Vec<String>> {
+        self.groups.clone()
+    }
+
+    /// Creates or replaces a group
+    pub fn set_group(&mut self, name: &str, ids: Vec<String>) -> Result<(), RegistryError> {
+        self.groups.insert(name.to_owned(), ids);
+        self.save()
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> Result<(), RegistryError> {
+        if self.groups.remove(name).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), RegistryError> {
+        let file = RegistryFile {
+            devices: self.devices(),
+            groups: self.groups.clone(),
+        };
+        Ok(store::save(&self.path, &file)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    fn registry_path(test: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir()
+            .join(format!("bedjet-registry-{test}-{nanos}"))
+            .join(DeviceRegistry::FILE_NAME)
+    }
+
+    fn device(id: &str, auto_connect: bool) -> KnownDevice {
+        KnownDevice {
+            id: id.to_owned(),
+            nickname: String::from("Upstairs"),
+            room: Some(String::from("Bedroom")),
+            preferred_adapter: None,
+            preferred_units: Some(TemperatureUnit::Fahrenheit),
+            auto_connect,
+        }
+    }
+
+    #[test]
+    fn changes_are_saved_and_loaded_back() {
+        let path = registry_path("save");
+        let mut registry = DeviceRegistry::load(&path).unwrap();
+        assert!(registry.devices().is_empty());
+
+        registry.add(device("a", true)).unwrap();
+        registry.add(device("b", false)).unwrap();
+        registry
+            .update(KnownDevice {
+                nickname: String::from("Guest room"),
+                ..device("b", false)
+            })
+            .unwrap();
+        registry.forget("a").unwrap();
+        registry
+            .set_group("Master bedroom", vec![String::from("b"), String::from("c")])
+            .unwrap();
+
+        let loaded = DeviceRegistry::load(&path).unwrap();
+        assert_eq!(loaded.devices(), registry.devices());
+        assert_eq!(loaded.groups(), registry.groups());
+        assert_eq!(loaded.get("b").unwrap().nickname, "Guest room");
+        assert!(loaded.auto_connect().is_empty());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn forgotten_devices_leave_their_groups() {
+        let path = registry_path("forget");
+        let mut registry = DeviceRegistry::load(&path).unwrap();
+        registry.add(device("a", false)).unwrap();
+        registry.add(device("b", false)).unwrap();
+        registry
+            .set_group("Master bedroom", vec![String::from("a"), String::from("b")])
+            .unwrap();
+        registry
+            .set_group("Guest room", vec![String::from("a")])
+            .unwrap();
+
+        registry.forget("a").unwrap();
+
+        let loaded = DeviceRegistry::load(&path).unwrap();
+        assert_eq!(
+            loaded.groups(),
+            BTreeMap::from([
+                (String::from("Guest room"), vec![]),
+                (String::from("Master bedroom"), vec![String::from("b")]),
+            ])
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rejects_duplicate_and_unknown_devices() {
+        let path = registry_path("reject");
+        let mut registry = DeviceRegistry::load(&path).unwrap();
+        registry.add(device("a", true)).unwrap();
+
+        assert!(matches!(
+            registry.add(device("a", false)),
+            Err(RegistryError::AlreadyKnown(_))
+        ));
+        assert!(matches!(
+            registry.update(device("b", false)),
+            Err(RegistryError::Unknown(_))
+        ));
+        assert!(matches!(
+            registry.forget("b"),
+            Err(RegistryError::Unknown(_))
+        ));
+        assert_eq!(registry.auto_connect(), vec![device("a", true)]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
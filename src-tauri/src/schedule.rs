@@ -0,0 +1,584 @@
+use crate::{
+    device::{BedJet, DeviceError},
+    proto::OperatingMode,
+    store::{self, StoreError},
+    transport::BedJetTransport,
+    Command, Encode, FanParam, TempParam,
+};
+use chrono::{Duration, Local, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+use thiserror::Error;
+use tokio::time;
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+#[serde(tag = "type", content = "content")]
+/// When a step of a schedule starts
+pub enum StepStart {
+    /// The next time the clock reaches this time of day, in local time
+    At { hours: u8, minutes: u8 },
+    /// This long after the previous step starts, or the schedule if it's the first step
+    After { minutes: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// A change a schedule makes to a device
+pub struct ScheduleStep {
+    pub start: StepStart,
+    pub mode: OperatingMode,
+    /// Left as the mode sets it if `None`
+    pub temperature: Option<TempParam>,
+    /// Left as the mode sets it if `None`
+    pub fan: Option<FanParam>,
+}
+
+impl ScheduleStep {
+    /// The commands that put a device into this step
+    pub fn commands(&self) -> Vec<Command> {
+        let mut commands: Vec<Command> = self
+            .mode
+            .button()
+            .map(Command::Button)
+            .into_iter()
+            .collect();
+        // Nothing else can be changed while the device is off
+        if self.mode != OperatingMode::Standby {
+            commands.extend(self.temperature.map(Command::SetTemp));
+            commands.extend(self.fan.map(Command::SetFan));
+        }
+        commands
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("A schedule needs at least one step")]
+    Empty,
+    #[error("Step {step} starts at {hours}:{minutes:02}, which isn't a time of day")]
+    InvalidTime { step: usize, hours: u8, minutes: u8 },
+    #[error("Step {step} switches to {mode:?}, which the device can't be told to do")]
+    InvalidMode { step: usize, mode: OperatingMode },
+    #[error("Step {step} can't be sent to the device: {reason}")]
+    InvalidStep { step: usize, reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// Steps run on the host one after another, for programs the device's biorhythm slots can't
+/// hold
+pub struct Schedule {
+    pub name: String,
+    pub steps: Vec<ScheduleStep>,
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<(), ScheduleError> {
+        if self.steps.is_empty() {
+            return Err(ScheduleError::Empty);
+        }
+
+        for (step, schedule_step) in self.steps.iter().enumerate() {
+            if let StepStart::At { hours, minutes } = schedule_step.start {
+                if hours > 23 || minutes > 59 {
+                    return Err(ScheduleError::InvalidTime {
+                        step,
+                        hours,
+                        minutes,
+                    });
+                }
+            }
+            if schedule_step.mode.button().is_none() {
+                return Err(ScheduleError::InvalidMode {
+                    step,
+                    mode: schedule_step.mode,
+                });
+            }
+            for command in schedule_step.commands() {
+                command
+                    .encode()
+                    .map_err(|error| ScheduleError::InvalidStep {
+                        step,
+                        reason: error.to_string(),
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// When each step starts, if the schedule is started at `start`
+    pub fn timeline(&self, start: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let mut previous = start;
+        self.steps
+            .iter()
+            .map(|step| {
+                previous = match step.start {
+                    StepStart::At { hours, minutes } => {
+                        let time = NaiveTime::from_hms_opt(hours.into(), minutes.into(), 0)
+                            .unwrap_or_default();
+                        let at = previous.date().and_time(time);
+                        if at < previous {
+                            at + Duration::days(1)
+                        } else {
+                            at
+                        }
+                    }
+                    StepStart::After { minutes } => previous + Duration::minutes(minutes.into()),
+                };
+                previous
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+/// A schedule running on a device
+pub struct ScheduleRun {
+    pub device_id: String,
+    pub schedule: Schedule,
+    /// When the schedule was started, in local time
+    #[typeshare(serialized_as = "String")]
+    pub started: NaiveDateTime,
+    /// The last step sent to the device, so it isn't sent again when the schedule resumes
+    pub applied_step: Option<usize>,
+}
+
+impl ScheduleRun {
+    pub fn new(device_id: String, schedule: Schedule, started: NaiveDateTime) -> Self {
+        Self {
+            device_id,
+            schedule,
+            started,
+            applied_step: None,
+        }
+    }
+
+    /// The step that should be active at `now`, or `None` if the first hasn't started yet
+    pub fn step_at(&self, now: NaiveDateTime) -> Option<usize> {
+        self.schedule
+            .timeline(self.started)
+            .iter()
+            .rposition(|start| *start <= now)
+    }
+
+    /// When the next step after `now` starts, or `None` if every step has started
+    pub fn next_step_at(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.schedule
+            .timeline(self.started)
+            .into_iter()
+            .find(|start| *start > now)
+    }
+
+    pub fn progress(&self, now: NaiveDateTime) -> ScheduleProgressEvent {
+        ScheduleProgressEvent {
+            device_id: self.device_id.clone(),
+            name: self.schedule.name.clone(),
+            step: self.applied_step,
+            next_step_at: self.next_step_at(now),
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+/// Where a running schedule is at, as sent to the frontend
+pub struct ScheduleProgressEvent {
+    pub device_id: String,
+    pub name: String,
+    /// The step the device was last put into
+    pub step: Option<usize>,
+    #[typeshare(serialized_as = "Option<String>")]
+    pub next_step_at: Option<NaiveDateTime>,
+    /// Why the last step couldn't be applied
+    pub error: Option<String>,
+}
+
+/// Sends the steps of a schedule to a device as they come up, until every step has been sent.
+/// `on_step` is called with the run after every step, and the error if the step couldn't be
+/// applied.
+///
+/// Returns early if the device can't be reached or doesn't respond. The run remembers which step
+/// was applied last, so calling this again once the device is back carries on from there. A step
+/// the device can't take is reported and skipped, without counting as applied.
+pub async fn run_schedule<T, F>(
+    bedjet: &BedJet<T>,
+    run: &mut ScheduleRun,
+    mut on_step: F,
+) -> Result<(), DeviceError>
+where
+    T: BedJetTransport,
+    F: FnMut(&ScheduleRun, Option<&DeviceError>),
+{
+    let mut skipped = None;
+    loop {
+        let now = Local::now().naive_local();
+        // Steps that were missed while the device was away are skipped, only the latest matters
+        let step = run.step_at(now);
+        if let Some(index) = step.filter(|_| step != run.applied_step && step != skipped) {
+            match apply_step(bedjet, &run.schedule.steps[index]).await {
+                Ok(()) => {
+                    run.applied_step = step;
+                    on_step(run, None);
+                }
+                Err(error) if is_transient(&error) => {
+                    on_step(run, Some(&error));
+                    return Err(error);
+                }
+                // Trying again won't help, so carry on with the rest of the schedule
+                Err(error) => {
+                    skipped = step;
+                    on_step(run, Some(&error));
+                }
+            }
+        }
+
+        let Some(next) = run.next_step_at(now) else {
+            return Ok(());
+        };
+        time::sleep((next - now).to_std().unwrap_or_default()).await;
+    }
+}
+
+/// How long a device gets to report the mode a step changed it to
+const MODE_CHANGE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Sends the commands that put a device into a step. The device checks temperatures against the
+/// range of the mode it's in, so after changing mode the rest wait until it reports the new one
+async fn apply_step<T: BedJetTransport>(
+    bedjet: &BedJet<T>,
+    step: &ScheduleStep,
+) -> Result<(), DeviceError> {
+    let mut status = bedjet.subscribe_status();
+    let changing_mode = status
+        .borrow_and_update()
+        .is_some_and(|status| status.operating_mode != step.mode);
+
+    for command in step.commands() {
+        let is_mode = matches!(command, Command::Button(_));
+        bedjet.send_command(command).await?;
+
+        if is_mode && changing_mode {
+            let in_mode = status
+                .wait_for(|status| status.is_some_and(|status| status.operating_mode == step.mode));
+            // The status is sent by the device itself, so it never closes
+            let _ = time::timeout(MODE_CHANGE_TIMEOUT, in_mode)
+                .await
+                .map_err(|_| DeviceError::Timeout)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether sending the same step again could work, once the device is reachable and responding
+fn is_transient(error: &DeviceError) -> bool {
+    matches!(
+        error,
+        DeviceError::BluetoothError(_)
+            | DeviceError::MissingCharacteristic(_)
+            | DeviceError::Timeout
+    )
+}
+
+#[derive(Debug)]
+/// The schedules running on each device, saved after every change so they carry on after the
+/// app restarts
+pub struct ScheduleStore {
+    path: PathBuf,
+    runs: BTreeMap<String, ScheduleRun>,
+}
+
+impl ScheduleStore {
+    pub const FILE_NAME: &'static str = "schedules.json";
+
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let runs: Vec<ScheduleRun> = store::load(&path)?.unwrap_or_default();
+        let runs = runs
+            .into_iter()
+            .map(|run| (run.device_id.clone(), run))
+            .collect();
+        Ok(Self { path, runs })
+    }
+
+    pub fn runs(&self) -> Vec<ScheduleRun> {
+        self.runs.values().cloned().collect()
+    }
+
+    /// Adds or updates the schedule running on a device
+    pub fn insert(&mut self, run: ScheduleRun) -> Result<(), StoreError> {
+        self.runs.insert(run.device_id.clone(), run);
+        self.save()
+    }
+
+    pub fn remove(&mut self, device_id: &str) -> Result<Option<ScheduleRun>, StoreError> {
+        let run = self.runs.remove(device_id);
+        if run.is_some() {
+            self.save()?;
+        }
+        Ok(run)
+    }
+
+    fn save(&self) -> Result<(), StoreError> {
+        store::save(&self.path, &self.runs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        proto::ButtonCode, simulator::SimulatorTransport, temperature::Temperature,
+        transport::MemoryTransport,
+    };
+    use chrono::NaiveDate;
+    use std::sync::Arc;
+
+    fn at(day: u32, hours: u32, minutes: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 6, day)
+            .unwrap()
+            .and_hms_opt(hours, minutes, 0)
+            .unwrap()
+    }
+
+    fn step(start: StepStart, mode: OperatingMode) -> ScheduleStep {
+        ScheduleStep {
+            start,
+            mode,
+            temperature: None,
+            fan: None,
+        }
+    }
+
+    fn bedtime() -> Schedule {
+        Schedule {
+            name: String::from("Bedtime"),
+            steps: vec![
+                step(
+                    StepStart::At {
+                        hours: 23,
+                        minutes: 30,
+                    },
+                    OperatingMode::NormalHeat,
+                ),
+                step(StepStart::After { minutes: 90 }, OperatingMode::Cool),
+                step(
+                    StepStart::At {
+                        hours: 6,
+                        minutes: 0,
+                    },
+                    OperatingMode::Standby,
+                ),
+                step(
+                    StepStart::At {
+                        hours: 5,
+                        minutes: 0,
+                    },
+                    OperatingMode::Dry,
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn steps_start_at_the_next_matching_time() {
+        assert_eq!(
+            bedtime().timeline(at(1, 22, 0)),
+            vec![at(1, 23, 30), at(2, 1, 0), at(2, 6, 0), at(3, 5, 0)]
+        );
+    }
+
+    #[test]
+    fn finds_the_active_and_next_steps() {
+        let run = ScheduleRun::new(String::from("a"), bedtime(), at(1, 22, 0));
+
+        assert_eq!(run.step_at(at(1, 23, 0)), None);
+        assert_eq!(run.next_step_at(at(1, 23, 0)), Some(at(1, 23, 30)));
+        assert_eq!(run.step_at(at(2, 1, 0)), Some(1));
+        assert_eq!(run.next_step_at(at(2, 1, 0)), Some(at(2, 6, 0)));
+        assert_eq!(run.step_at(at(4, 0, 0)), Some(3));
+        assert_eq!(run.next_step_at(at(4, 0, 0)), None);
+    }
+
+    #[test]
+    fn rejects_steps_that_cant_be_sent() {
+        let mut schedule = bedtime();
+        assert_eq!(schedule.validate(), Ok(()));
+
+        schedule.steps[1].start = StepStart::At {
+            hours: 24,
+            minutes: 0,
+        };
+        assert!(matches!(
+            schedule.validate(),
+            Err(ScheduleError::InvalidTime { step: 1, .. })
+        ));
+
+        schedule.steps[1] = ScheduleStep {
+            fan: Some(FanParam::Step(20)),
+            ..step(StepStart::After { minutes: 10 }, OperatingMode::Cool)
+        };
+        assert!(matches!(
+            schedule.validate(),
+            Err(ScheduleError::InvalidStep { step: 1, .. })
+        ));
+
+        schedule.steps.clear();
+        assert_eq!(schedule.validate(), Err(ScheduleError::Empty));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn applies_the_current_step_and_resumes_after_a_disconnect() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = BedJet::new(transport.clone());
+        let cool = Temperature::from_celsius(20.0).unwrap();
+        let schedule = Schedule {
+            name: String::from("Nap"),
+            steps: vec![
+                step(StepStart::After { minutes: 0 }, OperatingMode::NormalHeat),
+                ScheduleStep {
+                    temperature: Some(TempParam::Celsius(cool)),
+                    ..step(StepStart::After { minutes: 30 }, OperatingMode::Cool)
+                },
+                step(StepStart::After { minutes: 600 }, OperatingMode::Standby),
+            ],
+        };
+        let started = Local::now().naive_local() - Duration::hours(1);
+        let mut run = ScheduleRun::new(String::from("a"), schedule, started);
+
+        transport.drop_connection();
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            run_schedule(&bedjet, &mut run, |_, _| {}),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(result, Err(DeviceError::BluetoothError(_))));
+        assert_eq!(run.applied_step, None);
+
+        transport.connect().await.unwrap();
+        let mut applied = Vec::new();
+        let _ = tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            run_schedule(&bedjet, &mut run, |run, error| {
+                applied.push((run.applied_step, error.is_some()))
+            }),
+        )
+        .await;
+
+        // Only the step that should be running now is sent
+        assert_eq!(applied, vec![(Some(1), false)]);
+        assert_eq!(
+            transport.take_writes(),
+            vec![
+                (
+                    BedJet::COMMANDS_UUID,
+                    Command::Button(ButtonCode::Cool).encode().unwrap()
+                ),
+                (
+                    BedJet::COMMANDS_UUID,
+                    Command::SetTemp(TempParam::Celsius(cool)).encode().unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn steps_are_only_applied_once_sent() {
+        // Connected, but without the characteristic commands are written to
+        let bedjet = BedJet::new(MemoryTransport::new());
+        let schedule = Schedule {
+            name: String::from("Nap"),
+            steps: vec![step(
+                StepStart::After { minutes: 0 },
+                OperatingMode::NormalHeat,
+            )],
+        };
+        let mut run = ScheduleRun::new(String::from("a"), schedule, Local::now().naive_local());
+
+        let mut reported = Vec::new();
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            run_schedule(&bedjet, &mut run, |run, error| {
+                reported.push((run.applied_step, error.is_some()))
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            result,
+            Err(DeviceError::MissingCharacteristic(BedJet::COMMANDS_UUID))
+        ));
+        assert_eq!(run.applied_step, None);
+        assert_eq!(reported, vec![(None, true)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mode_changes_are_reported_before_the_temperature_is_sent() {
+        let transport = SimulatorTransport::default();
+        transport
+            .device()
+            .apply_command(Command::Button(ButtonCode::Cool))
+            .unwrap();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let notifications = tokio::spawn({
+            let bedjet = bedjet.clone();
+            async move { bedjet.handle_notifications().await }
+        });
+        let mut status = bedjet.subscribe_status();
+        while !transport.memory().is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+        transport.advance(std::time::Duration::ZERO);
+        status.wait_for(Option::is_some).await.unwrap();
+
+        // Warmer than cooling allows, so it's only accepted once the device is heating
+        let warm = Temperature::from_celsius(40.0).unwrap();
+        let schedule = Schedule {
+            name: String::from("Warm up"),
+            steps: vec![ScheduleStep {
+                temperature: Some(TempParam::Celsius(warm)),
+                ..step(StepStart::After { minutes: 0 }, OperatingMode::NormalHeat)
+            }],
+        };
+        let mut run = ScheduleRun::new(String::from("a"), schedule, Local::now().naive_local());
+
+        let mut reported = Vec::new();
+        run_schedule(&bedjet, &mut run, |run, error| {
+            reported.push((run.applied_step, error.is_some()))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(reported, vec![(Some(0), false)]);
+        let status = transport.device().status();
+        assert_eq!(status.operating_mode, OperatingMode::NormalHeat);
+        assert_eq!(status.target_temp, warm);
+
+        notifications.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drives_the_simulator_through_steps() {
+        let transport = SimulatorTransport::default();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let schedule = Schedule {
+            name: String::from("Warm up"),
+            steps: vec![ScheduleStep {
+                fan: Some(FanParam::Step(4)),
+                ..step(StepStart::After { minutes: 0 }, OperatingMode::ExtendedHeat)
+            }],
+        };
+        let mut run = ScheduleRun::new(String::from("a"), schedule, Local::now().naive_local());
+
+        run_schedule(&bedjet, &mut run, |_, _| {}).await.unwrap();
+
+        let status = transport.device().status();
+        assert_eq!(status.operating_mode, OperatingMode::ExtendedHeat);
+        assert_eq!(status.fan_step, 4);
+    }
+}
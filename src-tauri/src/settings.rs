@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[typeshare]
+#[serde(default)]
+/// App-wide preferences
+pub struct Settings {
+    /// Set each device's clock to local time when it connects, and keep it there
+    pub sync_clock: bool,
+}
+
+impl Settings {
+    pub const FILE_NAME: &'static str = "settings.json";
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { sync_clock: true }
+    }
+}
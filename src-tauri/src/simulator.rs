@@ -0,0 +1,776 @@
+use crate::{
+    device::{BedJet, DeviceError},
+    firmware::FirmwareInfo,
+    proto::{
+        ButtonCode, DeviceSetting, DeviceStatus, OperatingMode, ParameterCode, ShutDownCode,
+        UpdateStatus,
+    },
+    temperature::Temperature,
+    transport::{BedJetTransport, MemoryTransport, NotificationStream},
+    Command, Decode, Encode, InterfaceError, Parameter, ParameterValue, SetParamKind, Version,
+    PARAM_LEN,
+};
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// How many bytes of the status packet fit into the first notification,
+/// the rest has to be read from the characteristic.
+const FIRST_FRAGMENT_LEN: usize = 19;
+const MAX_FAN_STEP: u8 = 19;
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The limits and defaults the device applies when entering a mode.
+/// Temperatures are in units of 0.5 degrees celsius.
+pub struct ModeProfile {
+    pub min_temp: u8,
+    pub max_temp: u8,
+    pub default_temp: u8,
+    pub default_fan: u8,
+    pub max_duration: Duration,
+}
+
+impl ModeProfile {
+    /// Approximations of what a BedJet 3 reports for each mode
+    pub fn for_mode(mode: OperatingMode) -> Self {
+        const fn hours(h: u64) -> Duration {
+            Duration::from_secs(h * 3600)
+        }
+        match mode {
+            OperatingMode::Standby | OperatingMode::Wait => Self {
+                min_temp: 38,
+                max_temp: 87,
+                default_temp: 64,
+                default_fan: 0,
+                max_duration: Duration::ZERO,
+            },
+            OperatingMode::NormalHeat => Self {
+                min_temp: 38,
+                max_temp: 87,
+                default_temp: 64,
+                default_fan: 9,
+                max_duration: hours(4),
+            },
+            OperatingMode::TurboHeat => Self {
+                min_temp: 38,
+                max_temp: 87,
+                default_temp: 87,
+                default_fan: 19,
+                max_duration: Duration::from_secs(10 * 60),
+            },
+            OperatingMode::ExtendedHeat => Self {
+                min_temp: 38,
+                max_temp: 87,
+                default_temp: 60,
+                default_fan: 9,
+                max_duration: hours(10),
+            },
+            OperatingMode::Cool => Self {
+                min_temp: 30,
+                max_temp: 66,
+                default_temp: 40,
+                default_fan: 9,
+                max_duration: hours(12),
+            },
+            OperatingMode::Dry => Self {
+                min_temp: 30,
+                max_temp: 66,
+                default_temp: 50,
+                default_fan: 19,
+                max_duration: hours(2),
+            },
+        }
+    }
+
+    fn clamp_temp(&self, temp: u8) -> u8 {
+        temp.clamp(self.min_temp, self.max_temp)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What gets saved by the `MemoryNStore` buttons
+pub struct MemorySlot {
+    pub mode: OperatingMode,
+    /// Stored in units of 0.5 degrees celsius
+    pub target_temp: u8,
+    pub fan_step: u8,
+    pub runtime: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A deterministic software model of a BedJet 3.
+///
+/// Feed it the bytes written to the command characteristic with [`SimulatedBedJet::apply`],
+/// move time forward with [`SimulatedBedJet::advance`], and read back the resulting [`DeviceStatus`].
+pub struct SimulatedBedJet {
+    pub name: String,
+    pub firmware: FirmwareInfo,
+    pub wifi_ssid: Option<String>,
+    pub wifi_password: Option<String>,
+    /// Whether connection tests can reach the configured network
+    pub wifi_reachable: bool,
+    /// The firmware a firmware update will install, if it's newer than what's running
+    pub available_firmware: Option<Version>,
+    mode: OperatingMode,
+    /// Stored in units of 0.5 degrees celsius
+    target_temp: u8,
+    fan_step: u8,
+    remaining: Duration,
+    /// The outlet temperature in degrees celsius
+    actual_temp: f32,
+    /// The room temperature in degrees celsius
+    ambient_temp: f32,
+    /// Time of day according to the device
+    clock: Duration,
+    memory: [Option<MemorySlot>; 3],
+    /// The settings that are turned on
+    settings: HashSet<DeviceSetting>,
+    /// The encoded value of every parameter that's been set
+    parameters: HashMap<ParameterCode, Vec<u8>>,
+    /// The reply to the last parameter request
+    extended_data: Vec<u8>,
+    update_status: UpdateStatus,
+    /// What `update_status` will step through, a second at a time
+    update_sequence: VecDeque<UpdateStatus>,
+    /// Set when the device restarts, dropping its connection
+    restarting: bool,
+}
+
+impl Default for SimulatedBedJet {
+    fn default() -> Self {
+        Self::new(21.0)
+    }
+}
+
+impl SimulatedBedJet {
+    pub fn new(ambient_temp: f32) -> Self {
+        let profile = ModeProfile::for_mode(OperatingMode::Standby);
+        Self {
+            name: String::from("BedJet Sim"),
+            firmware: FirmwareInfo {
+                main_board: Version {
+                    major: 3,
+                    minor: 2,
+                    patch: 1,
+                },
+                wifi_module: Some(Version {
+                    major: 1,
+                    minor: 4,
+                    patch: 0,
+                }),
+            },
+            mode: OperatingMode::Standby,
+            target_temp: profile.default_temp,
+            fan_step: profile.default_fan,
+            remaining: Duration::ZERO,
+            actual_temp: ambient_temp,
+            ambient_temp,
+            clock: Duration::ZERO,
+            memory: [None; 3],
+            settings: HashSet::from([DeviceSetting::RingOfLight]),
+            parameters: HashMap::new(),
+            extended_data: Vec::new(),
+            wifi_ssid: None,
+            wifi_password: None,
+            wifi_reachable: true,
+            available_firmware: None,
+            restarting: false,
+            update_status: UpdateStatus::Idle,
+            update_sequence: VecDeque::new(),
+        }
+    }
+
+    pub fn mode(&self) -> OperatingMode {
+        self.mode
+    }
+
+    pub fn memory_slot(&self, slot: usize) -> Option<MemorySlot> {
+        self.memory.get(slot).copied().flatten()
+    }
+
+    /// The time of day the device thinks it is, as (hours, minutes)
+    pub fn clock(&self) -> (u8, u8) {
+        let minutes = self.clock.as_secs() / 60;
+        ((minutes / 60 % 24) as u8, (minutes % 60) as u8)
+    }
+
+    /// What the device puts in the extended data characteristic after a parameter request
+    pub fn extended_data(&self) -> &[u8] {
+        &self.extended_data
+    }
+
+    pub fn setting(&self, setting: DeviceSetting) -> bool {
+        self.settings.contains(&setting)
+    }
+
+    fn set_setting(&mut self, setting: DeviceSetting, enabled: bool) {
+        if enabled {
+            self.settings.insert(setting);
+        } else {
+            self.settings.remove(&setting);
+        }
+    }
+
+    pub fn set_ambient_temp(&mut self, celsius: f32) {
+        self.ambient_temp = celsius;
+    }
+
+    fn profile(&self) -> ModeProfile {
+        ModeProfile::for_mode(self.mode)
+    }
+
+    fn enter_mode(&mut self, mode: OperatingMode) {
+        let profile = ModeProfile::for_mode(mode);
+        self.mode = mode;
+        self.target_temp = profile.default_temp;
+        self.fan_step = profile.default_fan;
+        self.remaining = profile.max_duration;
+    }
+
+    fn is_running(&self) -> bool {
+        !matches!(self.mode, OperatingMode::Standby | OperatingMode::Wait)
+    }
+
+    /// Applies the bytes of a single encoded [`Command`]
+    pub fn apply(&mut self, bytes: &[u8]) -> Result<(), InterfaceError> {
+        self.apply_command(Command::read_from(bytes)?)
+    }
+
+    pub fn apply_command(&mut self, command: Command) -> Result<(), InterfaceError> {
+        match command {
+            Command::Button(code) => self.press(code),
+            Command::SetTime { hours, minutes } => {
+                if self.is_running() {
+                    let requested = Duration::from_secs(hours as u64 * 3600 + minutes as u64 * 60);
+                    self.remaining = requested.min(self.profile().max_duration);
+                }
+            }
+            Command::SetTemp(temp) => {
+                if self.is_running() {
+                    self.target_temp = self
+                        .profile()
+                        .clamp_temp(temp.temperature()?.half_degrees());
+                }
+            }
+            Command::SetFan(fan) => {
+                let step = fan.step()?;
+                if self.is_running() {
+                    self.fan_step = step;
+                }
+            }
+            Command::SetClock { hours, minutes } => {
+                if hours > 23 || minutes > 59 {
+                    return Err(InterfaceError::InvalidParameter);
+                }
+                self.clock = Duration::from_secs(hours as u64 * 3600 + minutes as u64 * 60);
+            }
+            Command::SetParam(param) => {
+                if let SetParamKind::DeviceName(name) = &param {
+                    self.name = name.clone();
+                }
+                self.parameters.insert(param.code()?, param.encode()?);
+            }
+            Command::GetParam(code) => {
+                self.extended_data = match code {
+                    ParameterCode::DeviceName => {
+                        SetParamKind::DeviceName(self.name.clone()).encode()?
+                    }
+                    ParameterCode::FirmwareVersionCodes => self.firmware_reply()?,
+                    _ => self.parameters.get(&code).cloned().unwrap_or_else(|| {
+                        // Parameters that were never set read back as zeros
+                        let mut reply = vec![code as u8, PARAM_LEN as u8];
+                        reply.resize(PARAM_LEN + 2, 0);
+                        reply
+                    }),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn firmware_reply(&self) -> Result<Vec<u8>, InterfaceError> {
+        let versions = std::iter::once(self.firmware.main_board)
+            .chain(self.firmware.wifi_module)
+            .collect();
+        Parameter {
+            code: ParameterCode::FirmwareVersionCodes,
+            value: ParameterValue::FirmwareVersionCodes(versions),
+        }
+        .encode()
+    }
+
+    fn press(&mut self, code: ButtonCode) {
+        match code {
+            ButtonCode::Stop => self.enter_mode(OperatingMode::Standby),
+            ButtonCode::Cool => self.enter_mode(OperatingMode::Cool),
+            ButtonCode::Heat => self.enter_mode(OperatingMode::NormalHeat),
+            ButtonCode::Turbo => self.enter_mode(OperatingMode::TurboHeat),
+            ButtonCode::Dry => self.enter_mode(OperatingMode::Dry),
+            ButtonCode::ExternalHeat => self.enter_mode(OperatingMode::ExtendedHeat),
+            ButtonCode::FanUp if self.is_running() => {
+                self.fan_step = self.fan_step.saturating_add(1).min(MAX_FAN_STEP)
+            }
+            ButtonCode::FanDown if self.is_running() => {
+                self.fan_step = self.fan_step.saturating_sub(1)
+            }
+            ButtonCode::TempUp1C => self.nudge_temp(2),
+            ButtonCode::TempDown1C => self.nudge_temp(-2),
+            // A degree fahrenheit is close enough to half a degree celsius
+            ButtonCode::TempUp1F => self.nudge_temp(1),
+            ButtonCode::TempDown1F => self.nudge_temp(-1),
+            ButtonCode::Memory1Recall => self.recall(0),
+            ButtonCode::Memory2Recall => self.recall(1),
+            ButtonCode::Memory3Recall => self.recall(2),
+            ButtonCode::Memory1Store => self.store(0),
+            ButtonCode::Memory2Store => self.store(1),
+            ButtonCode::Memory3Store => self.store(2),
+            ButtonCode::SetLowPowerMode => self.set_setting(DeviceSetting::LowPower, true),
+            ButtonCode::SetNormalPowerMode => self.set_setting(DeviceSetting::LowPower, false),
+            ButtonCode::EnableRingOfLight => self.set_setting(DeviceSetting::RingOfLight, true),
+            ButtonCode::DisableRingOfLight => self.set_setting(DeviceSetting::RingOfLight, false),
+            ButtonCode::MuteBeeper => self.set_setting(DeviceSetting::BeeperMuted, true),
+            ButtonCode::UnmuteBeeper => self.set_setting(DeviceSetting::BeeperMuted, false),
+            ButtonCode::StartConnectionTest => self.start_connection_test(),
+            ButtonCode::StartFirmwareUpdate => self.start_firmware_update(),
+            // Everything else doesn't change anything we model
+            _ => {}
+        }
+    }
+
+    fn start_connection_test(&mut self) {
+        let outcome = if self.wifi_ssid.is_none() {
+            UpdateStatus::NoWiFiConfig
+        } else if !self.wifi_reachable {
+            UpdateStatus::UnableToConnect
+        } else {
+            UpdateStatus::ConnectionTestOK
+        };
+
+        self.update_sequence =
+            VecDeque::from([UpdateStatus::Starting, UpdateStatus::ConnectingToAP]);
+        if outcome == UpdateStatus::ConnectionTestOK {
+            self.update_sequence
+                .extend([UpdateStatus::GotIPAddress, UpdateStatus::CheckingConnection]);
+        }
+        self.update_sequence.push_back(outcome);
+    }
+
+    fn start_firmware_update(&mut self) {
+        if self.wifi_ssid.is_none() {
+            self.update_sequence = VecDeque::from([UpdateStatus::NoWiFiConfig]);
+            return;
+        }
+        if !self.wifi_reachable {
+            self.update_sequence =
+                VecDeque::from([UpdateStatus::Starting, UpdateStatus::UnableToConnect]);
+            return;
+        }
+
+        self.update_sequence = VecDeque::from([
+            UpdateStatus::Starting,
+            UpdateStatus::ConnectingToAP,
+            UpdateStatus::GotIPAddress,
+            UpdateStatus::CheckingForUpdate,
+        ]);
+        match self.available_firmware {
+            Some(version) if version > self.firmware.main_board => {
+                self.update_sequence.extend([
+                    UpdateStatus::Updating,
+                    UpdateStatus::Updating,
+                    UpdateStatus::RestartingBedJet,
+                ]);
+            }
+            _ => self.update_sequence.push_back(UpdateStatus::NoUpdateNeeded),
+        }
+    }
+
+    /// Returns true once if the device restarted since the last call
+    pub fn take_restart(&mut self) -> bool {
+        std::mem::take(&mut self.restarting)
+    }
+
+    fn restart(&mut self) {
+        if let Some(version) = self.available_firmware.take() {
+            self.firmware.main_board = version;
+        }
+        self.enter_mode(OperatingMode::Standby);
+        self.update_status = UpdateStatus::Idle;
+        self.restarting = true;
+    }
+
+    fn nudge_temp(&mut self, half_degrees: i16) {
+        if !self.is_running() {
+            return;
+        }
+        let profile = self.profile();
+        let temp = (self.target_temp as i16 + half_degrees)
+            .clamp(profile.min_temp as i16, profile.max_temp as i16);
+        self.target_temp = temp as u8;
+    }
+
+    fn store(&mut self, slot: usize) {
+        if !self.is_running() {
+            return;
+        }
+        self.memory[slot] = Some(MemorySlot {
+            mode: self.mode,
+            target_temp: self.target_temp,
+            fan_step: self.fan_step,
+            runtime: self.remaining,
+        });
+    }
+
+    fn recall(&mut self, slot: usize) {
+        let Some(saved) = self.memory[slot] else {
+            return;
+        };
+        self.enter_mode(saved.mode);
+        let profile = self.profile();
+        self.target_temp = profile.clamp_temp(saved.target_temp);
+        self.fan_step = saved.fan_step.min(MAX_FAN_STEP);
+        self.remaining = saved.runtime.min(profile.max_duration);
+    }
+
+    /// Moves the simulation forward, a second at a time
+    pub fn advance(&mut self, elapsed: Duration) {
+        let mut left = elapsed;
+        while !left.is_zero() {
+            let step = left.min(Duration::from_secs(1));
+            self.step(step);
+            left -= step;
+        }
+    }
+
+    fn step(&mut self, dt: Duration) {
+        // Restarting is the last thing the device reports before it goes away
+        if self.update_status == UpdateStatus::RestartingBedJet {
+            self.restart();
+        } else if let Some(update_status) = self.update_sequence.pop_front() {
+            self.update_status = update_status;
+        }
+
+        self.clock += dt;
+        if self.clock >= DAY {
+            self.clock -= DAY;
+        }
+
+        if self.is_running() {
+            self.remaining = self.remaining.saturating_sub(dt);
+            if self.remaining.is_zero() {
+                match self.mode {
+                    // Turbo only lasts a few minutes before dropping back to regular heat
+                    OperatingMode::TurboHeat => {
+                        let target_temp = self.target_temp;
+                        self.enter_mode(OperatingMode::NormalHeat);
+                        self.target_temp = self.profile().clamp_temp(target_temp);
+                    }
+                    _ => self.enter_mode(OperatingMode::Standby),
+                }
+            }
+        }
+
+        // The outlet approaches the target when heating, otherwise it just blows room air.
+        // More air moves the temperature faster.
+        let goal = match self.mode {
+            OperatingMode::NormalHeat
+            | OperatingMode::TurboHeat
+            | OperatingMode::ExtendedHeat
+            | OperatingMode::Dry => self.target_temp as f32 / 2.0,
+            _ => self.ambient_temp,
+        };
+        let time_constant = if self.is_running() {
+            60.0 * (MAX_FAN_STEP as f32 + 1.0) / (self.fan_step as f32 + 1.0)
+        } else {
+            300.0
+        };
+        let factor = 1.0 - (-dt.as_secs_f32() / time_constant).exp();
+        self.actual_temp += (goal - self.actual_temp) * factor;
+    }
+
+    pub fn status(&self) -> DeviceStatus {
+        let profile = self.profile();
+        let remaining = self.remaining.as_secs();
+        let max_duration = profile.max_duration.as_secs();
+
+        DeviceStatus {
+            remaining_hours: (remaining / 3600) as u8,
+            remaining_minutes: (remaining / 60 % 60) as u8,
+            remaining_seconds: (remaining % 60) as u8,
+            actual_temp: to_temperature(self.actual_temp),
+            target_temp: Temperature::from_half_degrees(self.target_temp),
+            operating_mode: self.mode,
+            fan_step: self.fan_step,
+            max_duration_hours: (max_duration / 3600) as u8,
+            max_duration_minutes: (max_duration / 60 % 60) as u8,
+            min_target_temp: Temperature::from_half_degrees(profile.min_temp),
+            max_target_temp: Temperature::from_half_degrees(profile.max_temp),
+            ambient_temp: to_temperature(self.ambient_temp),
+            shutdown_code: ShutDownCode::Normal,
+            update_status: self.update_status,
+            unknown: [0; 10],
+        }
+    }
+
+    /// Splits the status packet the way the device does over BLE.
+    ///
+    /// Returns the notification, whose leading byte flags that more data follows,
+    /// and the remainder that has to be read from the status characteristic.
+    pub fn status_fragments(&self) -> (Vec<u8>, Vec<u8>) {
+        let packet = self.status().encode().expect("Writing to a Vec can't fail");
+        let (first, rest) = packet.split_at(FIRST_FRAGMENT_LEN);
+
+        let mut notification = vec![1];
+        notification.extend_from_slice(first);
+
+        (notification, rest.to_vec())
+    }
+}
+
+fn to_temperature(celsius: f32) -> Temperature {
+    Temperature::from_half_degrees((celsius * 2.0).round().clamp(0.0, u8::MAX as f32) as u8)
+}
+
+#[derive(Debug, Clone)]
+/// A [`BedJetTransport`] backed by a [`SimulatedBedJet`].
+///
+/// Writes to the command characteristic are applied to the simulation,
+/// and every change is published as a status notification.
+pub struct SimulatorTransport {
+    device: Arc<Mutex<SimulatedBedJet>>,
+    memory: MemoryTransport,
+}
+
+impl Default for SimulatorTransport {
+    fn default() -> Self {
+        Self::new(SimulatedBedJet::default())
+    }
+}
+
+impl SimulatorTransport {
+    pub fn new(device: SimulatedBedJet) -> Self {
+        let memory = MemoryTransport::with_bedjet_characteristics();
+
+        let transport = Self {
+            device: Arc::new(Mutex::new(device)),
+            memory,
+        };
+        transport.publish();
+        transport
+    }
+
+    /// Gives access to the simulation, changes made here aren't published until the next
+    /// command or call to [`SimulatorTransport::advance`]
+    pub fn device(&self) -> std::sync::MutexGuard<'_, SimulatedBedJet> {
+        self.device.lock().unwrap()
+    }
+
+    /// The underlying transport, useful for inspecting what was written
+    pub fn memory(&self) -> &MemoryTransport {
+        &self.memory
+    }
+
+    /// Moves the simulation forward and publishes the new status.
+    /// If the device restarted along the way, the connection is dropped
+    pub fn advance(&self, elapsed: Duration) {
+        let restarted = {
+            let mut device = self.device();
+            device.advance(elapsed);
+            device.take_restart()
+        };
+
+        if restarted {
+            self.memory.drop_connection();
+        } else {
+            self.publish();
+        }
+    }
+
+    fn publish(&self) {
+        let (notification, rest, name, extended_data) = {
+            let device = self.device();
+            let (notification, rest) = device.status_fragments();
+            (
+                notification,
+                rest,
+                device.name.clone(),
+                device.extended_data.clone(),
+            )
+        };
+
+        // The device zero pads its name out to the full parameter length
+        let mut name = name.into_bytes();
+        name.resize(PARAM_LEN.max(name.len()), 0);
+        self.memory.set_value(BedJet::FRIENDLY_NAME_UUID, name);
+        self.memory
+            .set_value(BedJet::EXTENDED_DATA_UUID, extended_data);
+        self.memory.set_value(BedJet::DEVICE_STATUS_UUID, rest);
+        self.memory.notify(BedJet::DEVICE_STATUS_UUID, notification);
+    }
+}
+
+#[async_trait]
+impl BedJetTransport for SimulatorTransport {
+    async fn connect(&self) -> Result<(), DeviceError> {
+        self.memory.connect().await?;
+        self.publish();
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<(), DeviceError> {
+        self.memory.disconnect().await
+    }
+
+    async fn is_connected(&self) -> Result<bool, DeviceError> {
+        self.memory.is_connected().await
+    }
+
+    async fn read(&self, uuid: Uuid) -> Result<Vec<u8>, DeviceError> {
+        self.memory.read(uuid).await
+    }
+
+    async fn write(&self, uuid: Uuid, data: &[u8]) -> Result<(), DeviceError> {
+        self.memory.write(uuid, data).await?;
+
+        match uuid {
+            BedJet::COMMANDS_UUID => {
+                self.device().apply(data)?;
+                self.publish();
+            }
+            BedJet::WIFI_SSID_UUID => {
+                self.device().wifi_ssid = Some(String::from_utf8_lossy(data).into_owned())
+            }
+            BedJet::WIFI_PASSWORD_UUID => {
+                self.device().wifi_password = Some(String::from_utf8_lossy(data).into_owned())
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn subscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        self.memory.subscribe(uuid).await
+    }
+
+    async fn unsubscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        self.memory.unsubscribe(uuid).await
+    }
+
+    async fn notifications(&self) -> Result<NotificationStream, DeviceError> {
+        self.memory.notifications().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{proto::CommandClass, FanParam, TempParam};
+
+    #[tokio::test]
+    async fn commands_drive_status_through_bedjet() {
+        let transport = SimulatorTransport::default();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+
+        let listener = tokio::spawn({
+            let bedjet = bedjet.clone();
+            async move { bedjet.handle_notifications().await }
+        });
+        while !transport.memory().is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+
+        for command in [
+            Command::Button(ButtonCode::Heat),
+            Command::SetTemp(TempParam::Celsius(Temperature::from_celsius(50.0).unwrap())),
+            Command::SetFan(FanParam::Step(14)),
+            Command::SetTime {
+                hours: 1,
+                minutes: 30,
+            },
+        ] {
+            bedjet.send_command(command).await.unwrap();
+        }
+        tokio::task::yield_now().await;
+
+        let status = bedjet.get_status().await.unwrap();
+        assert_eq!(status.operating_mode, OperatingMode::NormalHeat);
+        // Clamped to the maximum for heat
+        assert_eq!(status.target_temp.half_degrees(), 87);
+        assert_eq!(status.fan_step, 14);
+        assert_eq!((status.remaining_hours, status.remaining_minutes), (1, 30));
+
+        listener.abort();
+    }
+
+    #[test]
+    fn heats_towards_target_and_times_out() {
+        let mut sim = SimulatedBedJet::new(20.0);
+        sim.apply(&[CommandClass::Button as u8, ButtonCode::Heat as u8])
+            .unwrap();
+        sim.apply(&[CommandClass::SetTime as u8, 0, 10]).unwrap();
+
+        sim.advance(Duration::from_secs(5 * 60));
+        let status = sim.status();
+        assert!(status.actual_temp.celsius() > 20.0 && status.actual_temp <= status.target_temp);
+        assert_eq!(status.remaining_minutes, 5);
+
+        sim.advance(Duration::from_secs(5 * 60));
+        assert_eq!(sim.mode(), OperatingMode::Standby);
+    }
+
+    #[test]
+    fn turbo_falls_back_to_heat() {
+        let mut sim = SimulatedBedJet::default();
+        sim.apply(&[CommandClass::Button as u8, ButtonCode::Turbo as u8])
+            .unwrap();
+
+        sim.advance(Duration::from_secs(10 * 60));
+
+        let status = sim.status();
+        assert_eq!(status.operating_mode, OperatingMode::NormalHeat);
+        assert_eq!(status.remaining_hours, 4);
+    }
+
+    #[test]
+    fn memory_slots_store_and_recall() {
+        let mut sim = SimulatedBedJet::default();
+        sim.apply(&[CommandClass::Button as u8, ButtonCode::Cool as u8])
+            .unwrap();
+        sim.apply(&[CommandClass::SetFan as u8, 3]).unwrap();
+        sim.apply(&[CommandClass::Button as u8, ButtonCode::Memory2Store as u8])
+            .unwrap();
+        sim.apply(&[CommandClass::Button as u8, ButtonCode::Stop as u8])
+            .unwrap();
+
+        sim.apply(&[CommandClass::Button as u8, ButtonCode::Memory2Recall as u8])
+            .unwrap();
+
+        let status = sim.status();
+        assert_eq!(status.operating_mode, OperatingMode::Cool);
+        assert_eq!(status.fan_step, 3);
+        assert_eq!(sim.memory_slot(0), None);
+    }
+
+    #[test]
+    fn settings_are_toggled_by_their_buttons() {
+        let mut sim = SimulatedBedJet::default();
+        for setting in [
+            DeviceSetting::LowPower,
+            DeviceSetting::BeeperMuted,
+            DeviceSetting::RingOfLight,
+        ] {
+            sim.apply_command(Command::Button(setting.button(true)))
+                .unwrap();
+            assert!(sim.setting(setting));
+            sim.apply_command(Command::Button(setting.button(false)))
+                .unwrap();
+            assert!(!sim.setting(setting));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("Couldn't access {path}: {source}")]
+    Io { path: String, source: io::Error },
+    #[error("{path} is corrupt: {source}")]
+    Corrupt {
+        path: String,
+        source: serde_json::Error,
+    },
+}
+
+/// Reads a value saved with [`save`], or `None` if nothing has been saved there yet
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, StoreError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(io_error(path, source)),
+    };
+
+    serde_json::from_slice(&bytes)
+        .map(Some)
+        .map_err(|source| StoreError::Corrupt {
+            path: path.display().to_string(),
+            source,
+        })
+}
+
+/// Saves a value as JSON. It's written to a temporary file first, so a crash partway through
+/// can't lose what was there before
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), StoreError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|source| io_error(parent, source))?;
+    }
+    let json = serde_json::to_vec_pretty(value).map_err(|source| StoreError::Corrupt {
+        path: path.display().to_string(),
+        source,
+    })?;
+
+    let temporary = path.with_extension("json.tmp");
+    fs::write(&temporary, json).map_err(|source| io_error(&temporary, source))?;
+    fs::rename(&temporary, path).map_err(|source| io_error(path, source))
+}
+
+/// Moves a file that couldn't be loaded out of the way, so starting over doesn't overwrite it.
+/// Files set aside earlier are kept too, numbering each one after the first.
+/// Returns where it was moved to
+pub fn set_aside(path: &Path) -> Result<PathBuf, StoreError> {
+    let aside = (0..)
+        .map(|n| match n {
+            0 => path.with_extension("json.corrupt"),
+            n => path.with_extension(format!("json.corrupt.{n}")),
+        })
+        .find(|aside| !aside.exists())
+        .expect("there's always a free name");
+    fs::rename(path, &aside).map_err(|source| io_error(path, source))?;
+    Ok(aside)
+}
+
+fn io_error(path: &Path, source: io::Error) -> StoreError {
+    StoreError::Io {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn corrupt_files_are_set_aside() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("bedjet-store-{nanos}"));
+        let path = dir.join("settings.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        assert!(matches!(
+            load::<Vec<u8>>(&path),
+            Err(StoreError::Corrupt { .. })
+        ));
+        let aside = set_aside(&path).unwrap();
+
+        assert_eq!(load::<Vec<u8>>(&path).unwrap(), None);
+        assert_eq!(fs::read_to_string(&aside).unwrap(), "{ not json");
+
+        // Setting aside another one keeps the first
+        fs::write(&path, "[1, 2").unwrap();
+        let again = set_aside(&path).unwrap();
+
+        assert_ne!(again, aside);
+        assert_eq!(fs::read_to_string(&aside).unwrap(), "{ not json");
+        assert_eq!(fs::read_to_string(again).unwrap(), "[1, 2");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
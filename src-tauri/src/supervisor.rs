@@ -0,0 +1,360 @@
+use crate::{
+    device::BedJet,
+    transport::{BedJetTransport, NotificationStream},
+};
+use futures::FutureExt;
+use serde::Serialize;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{watch, Notify},
+    time,
+};
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[typeshare]
+#[serde(tag = "type", content = "content")]
+/// Where a supervised BedJet's connection is at
+pub enum ConnectionState {
+    /// Making the first connection
+    Connecting,
+    /// Connected and receiving status notifications
+    Connected,
+    /// The connection was lost, and this is the attempt being made to get it back
+    Reconnecting { attempt: u32 },
+    /// Every reconnection attempt failed, so the supervisor gave up
+    Failed,
+    /// The connection was closed on purpose, or the adapter it was on went away
+    Disconnected,
+}
+
+/// Waits until a supervised device is gone for good, because its supervisor gave up or was
+/// stopped. Tasks working with the device can end then, since it isn't coming back
+pub async fn gone(state: &mut watch::Receiver<ConnectionState>) {
+    let _ = state
+        .wait_for(|state| {
+            matches!(
+                state,
+                ConnectionState::Failed | ConnectionState::Disconnected
+            )
+        })
+        .await;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[typeshare]
+/// A connection state change for a specific device, as sent to the frontend
+pub struct ConnectionStateEvent {
+    pub id: String,
+    pub state: ConnectionState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How long to wait between reconnection attempts
+pub struct ReconnectPolicy {
+    /// The delay before the first attempt
+    pub initial_delay: Duration,
+    /// The delay never grows past this, before jitter is applied
+    pub max_delay: Duration,
+    /// How much the delay grows by after each failed attempt
+    pub multiplier: f64,
+    /// The fraction of the delay that is randomized, so several devices dropping out together
+    /// don't all retry at the same moment. 0.2 means the delay varies by up to 20% either way
+    pub jitter: f64,
+    /// Gives up after this many failed attempts, or never if `None`
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: Some(10),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// The delay before an attempt, counting from 1.
+    /// `sample` picks where in the jitter range the delay lands, from 0 to 1
+    pub fn delay(&self, attempt: u32, sample: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let base = (self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_delay.as_secs_f64());
+        let jitter = base * self.jitter * (sample.clamp(0.0, 1.0) * 2.0 - 1.0);
+        Duration::from_secs_f64((base + jitter).max(0.0))
+    }
+
+    fn is_exhausted(&self, attempt: u32) -> bool {
+        self.max_attempts.is_some_and(|max| attempt > max)
+    }
+}
+
+/// A number between 0 and 1 for jitter. It doesn't need to be good, just different each time
+fn random_sample() -> f64 {
+    let hash = RandomState::new().build_hasher().finish();
+    hash as f64 / u64::MAX as f64
+}
+
+#[derive(Debug)]
+/// Keeps a BedJet connected, reconnecting with backoff whenever the connection drops.
+///
+/// A drop is noticed when the notification stream ends, or when [`Supervisor::disconnected`]
+/// is called, for platforms that report disconnects through adapter events instead.
+pub struct Supervisor<T: BedJetTransport> {
+    bedjet: Arc<BedJet<T>>,
+    policy: ReconnectPolicy,
+    state: watch::Sender<ConnectionState>,
+    disconnected: Notify,
+}
+
+impl<T: BedJetTransport> Supervisor<T> {
+    pub fn new(bedjet: Arc<BedJet<T>>, policy: ReconnectPolicy) -> Self {
+        let (state, _) = watch::channel(ConnectionState::Connecting);
+
+        Self {
+            bedjet,
+            policy,
+            state,
+            disconnected: Notify::new(),
+        }
+    }
+
+    pub fn bedjet(&self) -> &Arc<BedJet<T>> {
+        &self.bedjet
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Every change in connection state
+    pub fn subscribe_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    /// Tells the supervisor the device went away. Reports made before the device is connected
+    /// again are dropped, so a late one can't cut short the connection that replaced the one it
+    /// was about
+    pub fn disconnected(&self) {
+        self.disconnected.notify_one();
+    }
+
+    /// Connects, and keeps reconnecting until the policy gives up.
+    /// Returns [`ConnectionState::Failed`], so this is usually run as a task and aborted to stop
+    pub async fn run(&self) -> ConnectionState {
+        self.state.send_replace(ConnectionState::Connecting);
+        let mut attempt = 0;
+
+        loop {
+            // Connecting also discovers services, which may have changed if the device rebooted.
+            // The attempt only counts as a success once status notifications are subscribed to
+            if let Some(stream) = self.set_up().await {
+                // Anything reported before now was about an earlier connection
+                let _ = self.disconnected.notified().now_or_never();
+                attempt = 0;
+                self.state.send_replace(ConnectionState::Connected);
+                self.stay_connected(stream).await;
+            }
+
+            attempt += 1;
+            if self.policy.is_exhausted(attempt) {
+                self.state.send_replace(ConnectionState::Failed);
+                return ConnectionState::Failed;
+            }
+            self.state
+                .send_replace(ConnectionState::Reconnecting { attempt });
+            time::sleep(self.policy.delay(attempt, random_sample())).await;
+        }
+    }
+
+    /// Connects and subscribes to the device status, returning the notifications if both worked
+    async fn set_up(&self) -> Option<NotificationStream> {
+        self.bedjet.transport().connect().await.ok()?;
+        self.bedjet.listen().await.ok()
+    }
+
+    /// Handles notifications until the connection drops
+    async fn stay_connected(&self, stream: NotificationStream) {
+        tokio::select! {
+            _ = self.bedjet.process_notifications(stream) => {}
+            _ = self.disconnected.notified() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MemoryTransport;
+
+    fn policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            jitter: 0.0,
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    /// Waits for the supervisor to change to a state, returning every state it went through
+    async fn wait_for(
+        states: &mut watch::Receiver<ConnectionState>,
+        target: ConnectionState,
+    ) -> Vec<ConnectionState> {
+        let mut seen = Vec::new();
+        while seen.last() != Some(&target) {
+            states.changed().await.unwrap();
+            seen.push(*states.borrow_and_update());
+        }
+        seen
+    }
+
+    #[test]
+    fn delay_backs_off_exponentially_up_to_the_maximum() {
+        let policy = policy(None);
+
+        assert_eq!(policy.delay(1, 0.5), Duration::from_secs(1));
+        assert_eq!(policy.delay(3, 0.5), Duration::from_secs(4));
+        assert_eq!(policy.delay(20, 0.5), Duration::from_secs(60));
+
+        let jittered = ReconnectPolicy {
+            jitter: 0.5,
+            ..policy
+        };
+        assert_eq!(jittered.delay(2, 0.0), Duration::from_secs(1));
+        assert_eq!(jittered.delay(2, 1.0), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reconnects_after_the_connection_drops() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let supervisor = Arc::new(Supervisor::new(bedjet, policy(None)));
+        let mut states = supervisor.subscribe_state();
+
+        let task = tokio::spawn({
+            let supervisor = supervisor.clone();
+            async move { supervisor.run().await }
+        });
+        wait_for(&mut states, ConnectionState::Connected).await;
+        while !transport.is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+
+        transport.drop_connection();
+
+        let seen = wait_for(&mut states, ConnectionState::Connected).await;
+        assert_eq!(
+            seen,
+            vec![
+                ConnectionState::Reconnecting { attempt: 1 },
+                ConnectionState::Connected
+            ]
+        );
+        // Status notifications are subscribed to again
+        while !transport.is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+
+        task.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reported_disconnects_trigger_a_reconnect() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let supervisor = Arc::new(Supervisor::new(bedjet, policy(None)));
+        let mut states = supervisor.subscribe_state();
+
+        let task = tokio::spawn({
+            let supervisor = supervisor.clone();
+            async move { supervisor.run().await }
+        });
+        wait_for(&mut states, ConnectionState::Connected).await;
+
+        supervisor.disconnected();
+
+        let seen = wait_for(&mut states, ConnectionState::Connected).await;
+        assert!(seen.contains(&ConnectionState::Reconnecting { attempt: 1 }));
+
+        task.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn disconnects_reported_while_reconnecting_are_ignored() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let supervisor = Arc::new(Supervisor::new(bedjet, policy(None)));
+        let mut states = supervisor.subscribe_state();
+
+        let task = tokio::spawn({
+            let supervisor = supervisor.clone();
+            async move { supervisor.run().await }
+        });
+        wait_for(&mut states, ConnectionState::Connected).await;
+
+        // The stream ends first, and the adapter reports the same drop afterwards
+        transport.drop_connection();
+        wait_for(&mut states, ConnectionState::Reconnecting { attempt: 1 }).await;
+        supervisor.disconnected();
+        wait_for(&mut states, ConnectionState::Connected).await;
+
+        time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(supervisor.state(), ConnectionState::Connected);
+        assert!(!states.has_changed().unwrap());
+
+        task.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn disconnects_reported_while_busy_are_kept() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let supervisor = Supervisor::new(bedjet.clone(), policy(None));
+        let stream = bedjet.listen().await.unwrap();
+
+        // Reported before the supervisor got around to waiting for it
+        supervisor.disconnected();
+
+        let result =
+            time::timeout(Duration::from_secs(60), supervisor.stay_connected(stream)).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failing_to_subscribe_counts_as_a_failed_attempt() {
+        // Connecting works, but there's no status characteristic to subscribe to
+        let transport = MemoryTransport::new();
+        let bedjet = Arc::new(BedJet::new(transport));
+        let supervisor = Supervisor::new(bedjet, policy(Some(3)));
+
+        let result = time::timeout(Duration::from_secs(60), supervisor.run()).await;
+
+        assert_eq!(result, Ok(ConnectionState::Failed));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_attempts() {
+        let transport = MemoryTransport::with_bedjet_characteristics();
+        transport.drop_connection();
+        transport.set_reachable(false);
+        let bedjet = Arc::new(BedJet::new(transport));
+        let supervisor = Supervisor::new(bedjet, policy(Some(3)));
+
+        let started = time::Instant::now();
+        let result = supervisor.run().await;
+
+        assert_eq!(result, ConnectionState::Failed);
+        assert_eq!(supervisor.state(), ConnectionState::Failed);
+        // Waited 1, 2, then 4 seconds between attempts
+        assert_eq!(started.elapsed(), Duration::from_secs(7));
+    }
+}
@@ -0,0 +1,589 @@
+use crate::{
+    device::BedJet,
+    proto::{DeviceStatus, OperatingMode},
+    supervisor::{self, ConnectionState},
+    temperature::Temperature,
+    transport::BedJetTransport,
+    Command, TempParam,
+};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use typeshare::typeshare;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[typeshare]
+/// One side of a dual-zone bed
+pub enum Zone {
+    /// Whose target temperature the follower's offset is measured from
+    Leader,
+    Follower,
+}
+
+impl Zone {
+    fn other(self) -> Self {
+        match self {
+            Self::Leader => Self::Follower,
+            Self::Follower => Self::Leader,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// What a device was sent, so the status updates it sends back aren't mistaken for someone
+/// changing it by hand
+struct Expected {
+    mode: OperatingMode,
+    remaining: Option<Duration>,
+    target_temp: Option<Temperature>,
+    deadline: Instant,
+}
+
+impl Expected {
+    fn matches(&self, status: &DeviceStatus) -> bool {
+        status.operating_mode == self.mode
+            && self.remaining.map_or(true, |remaining| {
+                timers_match(remaining, status.remaining())
+            })
+            && self
+                .target_temp
+                .map_or(true, |target| status.target_temp == target)
+    }
+}
+
+fn timers_match(a: Duration, b: Duration) -> bool {
+    a.abs_diff(b) <= ZoneSync::TIMER_TOLERANCE
+}
+
+#[derive(Debug)]
+/// Keeps two BedJets on the same bed in the same mode with the same time left, while each side
+/// keeps its own fan step and the follower keeps its target temperature at an offset from the
+/// leader's.
+///
+/// Feed it every status from both devices with [`ZoneSync::update`], and send the commands it
+/// returns. Commands it sent are remembered until the device reports back having applied them,
+/// so the two devices don't keep updating each other.
+pub struct ZoneSync {
+    /// The follower's target temperature minus the leader's, in half degrees celsius
+    offset: i16,
+    statuses: [Option<DeviceStatus>; 2],
+    expected: [Option<Expected>; 2],
+}
+
+impl ZoneSync {
+    /// Timers closer than this are considered the same, since the devices count down separately
+    pub const TIMER_TOLERANCE: Duration = Duration::from_secs(60);
+    /// How long a device has to apply what it was sent. Changes after that are treated as
+    /// someone else's, even if the device never got there
+    pub const SETTLE_TIME: Duration = Duration::from_secs(5);
+
+    /// `offset` is the follower's target temperature minus the leader's, in half degrees celsius
+    pub fn new(offset: i16) -> Self {
+        Self {
+            offset,
+            statuses: [None; 2],
+            expected: [None; 2],
+        }
+    }
+
+    /// The current offset, which changes when the follower's temperature is changed by hand
+    pub fn offset(&self) -> i16 {
+        self.offset
+    }
+
+    /// Takes a new status from one side, returning the commands to send to bring the other side
+    /// in line
+    pub fn update(
+        &mut self,
+        zone: Zone,
+        status: DeviceStatus,
+        now: Instant,
+    ) -> Vec<(Zone, Command)> {
+        let previous = self.statuses[zone.index()].replace(status);
+
+        let (echo, gave_up) = match self.expected[zone.index()].take() {
+            Some(expected) if expected.matches(&status) => (true, false),
+            Some(expected) if now < expected.deadline => {
+                // Still getting there
+                self.expected[zone.index()] = Some(expected);
+                return Vec::new();
+            }
+            Some(_) => (false, true),
+            None => (false, false),
+        };
+        if self.statuses[zone.other().index()].is_none() {
+            return Vec::new();
+        }
+        if gave_up {
+            // Whatever the device ended up doing, the other side follows it
+            return self.align(zone, now);
+        }
+
+        let Some(previous) = previous else {
+            // The first status from this side, so line the follower up with the leader
+            return self.align(Zone::Leader, now);
+        };
+        let target_changed = previous.target_temp != status.target_temp;
+        if echo {
+            // Our own change, but a mode change on the leader also moves its target temperature,
+            // and the follower may have only now reached the mode its temperature was held for
+            return match zone {
+                Zone::Leader if target_changed => self.align_temperature(now),
+                Zone::Follower => self.align_temperature(now),
+                _ => Vec::new(),
+            };
+        }
+
+        let mode_changed = previous.operating_mode != status.operating_mode;
+        // Timers count down on their own, so only a jump means someone set it
+        let timer_changed = !timers_match(previous.remaining(), status.remaining());
+        let mut commands = Vec::new();
+        if mode_changed || timer_changed {
+            commands = self.align(zone, now);
+        } else if target_changed {
+            match zone {
+                Zone::Leader => commands = self.align_temperature(now),
+                Zone::Follower => {
+                    if let (Some(leader), Some(follower)) = self.both() {
+                        self.offset = follower.target_temp.half_degrees() as i16
+                            - leader.target_temp.half_degrees() as i16;
+                    }
+                }
+            }
+        }
+        commands
+    }
+
+    /// Forgets what a device was sent, like when sending it failed
+    pub fn forget_expected(&mut self, zone: Zone) {
+        self.expected[zone.index()] = None;
+    }
+
+    fn both(&self) -> (Option<DeviceStatus>, Option<DeviceStatus>) {
+        (
+            self.statuses[Zone::Leader.index()],
+            self.statuses[Zone::Follower.index()],
+        )
+    }
+
+    /// Copies the mode and timer from one side to the other
+    fn align(&mut self, source: Zone, now: Instant) -> Vec<(Zone, Command)> {
+        let target = source.other();
+        let (Some(from), Some(to)) = (self.statuses[source.index()], self.statuses[target.index()])
+        else {
+            return Vec::new();
+        };
+
+        let mut commands = Vec::new();
+        let mode = from.operating_mode;
+        let mode_differs = mode != to.operating_mode;
+        if mode_differs {
+            let Some(button) = mode.button() else {
+                return Vec::new();
+            };
+            commands.push((target, Command::Button(button)));
+        }
+
+        let mut remaining = None;
+        // Changing mode resets the timer, so it always needs setting again
+        if mode != OperatingMode::Standby
+            && (mode_differs || !timers_match(from.remaining(), to.remaining()))
+        {
+            remaining = Some(from.remaining());
+            commands.push((
+                target,
+                Command::SetTime {
+                    hours: from.remaining_hours,
+                    minutes: from.remaining_minutes,
+                },
+            ));
+        }
+        if !commands.is_empty() {
+            self.expect(target, mode, remaining, None, now);
+        }
+
+        if source == Zone::Leader {
+            commands.extend(self.align_temperature(now));
+        }
+        commands
+    }
+
+    /// Sets the follower's target temperature from the leader's and the offset
+    fn align_temperature(&mut self, now: Instant) -> Vec<(Zone, Command)> {
+        let (Some(leader), Some(follower)) = self.both() else {
+            return Vec::new();
+        };
+        if leader.operating_mode == OperatingMode::Standby {
+            return Vec::new();
+        }
+        let pending = self.expected[Zone::Follower.index()];
+        // The device checks temperatures against the range of the mode it's in, so one for the
+        // mode it's on its way to waits until it reports being there
+        if pending.is_some_and(|expected| expected.mode != follower.operating_mode) {
+            return Vec::new();
+        }
+
+        // The follower is in the leader's mode, so it has the same range
+        let target = (leader.target_temp.half_degrees() as i16 + self.offset).clamp(
+            leader.min_target_temp.half_degrees() as i16,
+            leader.max_target_temp.half_degrees() as i16,
+        );
+        let target = Temperature::from_half_degrees(target as u8);
+        if follower.target_temp == target && pending.is_none() {
+            return Vec::new();
+        }
+
+        let mode = pending.map_or(follower.operating_mode, |expected| expected.mode);
+        let remaining = pending.and_then(|expected| expected.remaining);
+        self.expect(Zone::Follower, mode, remaining, Some(target), now);
+        vec![(Zone::Follower, Command::SetTemp(TempParam::Celsius(target)))]
+    }
+
+    fn expect(
+        &mut self,
+        zone: Zone,
+        mode: OperatingMode,
+        remaining: Option<Duration>,
+        target_temp: Option<Temperature>,
+        now: Instant,
+    ) {
+        self.expected[zone.index()] = Some(Expected {
+            mode,
+            remaining,
+            target_temp,
+            deadline: now + Self::SETTLE_TIME,
+        });
+    }
+}
+
+/// Keeps two BedJets in sync until either is [`gone`](supervisor::gone), going by their
+/// connection states. Both need to be handling notifications, so their statuses come through
+pub async fn sync_zones<T: BedJetTransport>(
+    leader: &BedJet<T>,
+    follower: &BedJet<T>,
+    mut leader_state: watch::Receiver<ConnectionState>,
+    mut follower_state: watch::Receiver<ConnectionState>,
+    sync: &mut ZoneSync,
+) {
+    let mut leader_status = leader.subscribe_status();
+    let mut follower_status = follower.subscribe_status();
+    let mut pending = vec![
+        (Zone::Leader, *leader_status.borrow_and_update()),
+        (Zone::Follower, *follower_status.borrow_and_update()),
+    ];
+
+    loop {
+        for (zone, status) in pending.drain(..) {
+            let Some(status) = status else {
+                continue;
+            };
+            for (target, command) in sync.update(zone, status, Instant::now()) {
+                let bedjet = match target {
+                    Zone::Leader => leader,
+                    Zone::Follower => follower,
+                };
+                if bedjet.send_command(command).await.is_err() {
+                    // The next status from it will be reconciled again
+                    sync.forget_expected(target);
+                    break;
+                }
+            }
+        }
+
+        let (zone, status) = tokio::select! {
+            changed = leader_status.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                (Zone::Leader, *leader_status.borrow_and_update())
+            }
+            changed = follower_status.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                (Zone::Follower, *follower_status.borrow_and_update())
+            }
+            _ = supervisor::gone(&mut leader_state) => return,
+            _ = supervisor::gone(&mut follower_state) => return,
+        };
+        pending.push((zone, status));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        proto::ButtonCode,
+        simulator::{SimulatedBedJet, SimulatorTransport},
+    };
+    use std::sync::Arc;
+
+    fn status(sim: &mut SimulatedBedJet, command: Command) -> DeviceStatus {
+        sim.apply_command(command).unwrap();
+        sim.status()
+    }
+
+    #[test]
+    fn mirrors_mode_and_timer_without_ping_pong() {
+        let now = Instant::now();
+        let mut leader = SimulatedBedJet::default();
+        let mut follower = SimulatedBedJet::default();
+        let mut sync = ZoneSync::new(2);
+        assert!(sync.update(Zone::Leader, leader.status(), now).is_empty());
+        assert!(sync
+            .update(Zone::Follower, follower.status(), now)
+            .is_empty());
+
+        // Someone presses cool on the leader's remote
+        let commands = sync.update(
+            Zone::Leader,
+            status(&mut leader, Command::Button(ButtonCode::Cool)),
+            now,
+        );
+        let target = Temperature::from_half_degrees(leader.status().target_temp.half_degrees() + 2);
+        assert_eq!(
+            commands,
+            vec![
+                (Zone::Follower, Command::Button(ButtonCode::Cool)),
+                (
+                    Zone::Follower,
+                    Command::SetTime {
+                        hours: 12,
+                        minutes: 0
+                    }
+                ),
+            ]
+        );
+
+        // The follower reports back as each command is applied, which isn't sent back. Once
+        // it's in the new mode it's sent the temperature
+        let mut sent = Vec::new();
+        let mut commands = commands;
+        while !commands.is_empty() {
+            let (zone, command) = commands.remove(0);
+            assert_eq!(zone, Zone::Follower);
+            let reported = status(&mut follower, command.clone());
+            commands.extend(sync.update(Zone::Follower, reported, now));
+            sent.push(command);
+        }
+        assert_eq!(
+            sent.last(),
+            Some(&Command::SetTemp(TempParam::Celsius(target)))
+        );
+        assert_eq!(follower.status().target_temp, target);
+        assert_eq!(follower.status().fan_step, leader.status().fan_step);
+
+        // Changing the follower's timer by hand goes to the leader
+        let commands = sync.update(
+            Zone::Follower,
+            status(
+                &mut follower,
+                Command::SetTime {
+                    hours: 2,
+                    minutes: 0,
+                },
+            ),
+            now,
+        );
+        assert_eq!(
+            commands,
+            vec![(
+                Zone::Leader,
+                Command::SetTime {
+                    hours: 2,
+                    minutes: 0
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn follower_temperature_changes_update_the_offset() {
+        let now = Instant::now();
+        let mut leader = SimulatedBedJet::default();
+        let mut follower = SimulatedBedJet::default();
+        leader
+            .apply_command(Command::Button(ButtonCode::Heat))
+            .unwrap();
+        follower
+            .apply_command(Command::Button(ButtonCode::Heat))
+            .unwrap();
+        let mut sync = ZoneSync::new(0);
+        sync.update(Zone::Leader, leader.status(), now);
+        sync.update(Zone::Follower, follower.status(), now);
+
+        let warmer =
+            Temperature::from_half_degrees(follower.status().target_temp.half_degrees() + 3);
+        let commands = sync.update(
+            Zone::Follower,
+            status(&mut follower, Command::SetTemp(TempParam::Celsius(warmer))),
+            now,
+        );
+        assert!(commands.is_empty());
+        assert_eq!(sync.offset(), 3);
+
+        // The leader moving carries the follower along, keeping the new offset
+        let leader_target =
+            Temperature::from_half_degrees(leader.status().target_temp.half_degrees() - 4);
+        let commands = sync.update(
+            Zone::Leader,
+            status(
+                &mut leader,
+                Command::SetTemp(TempParam::Celsius(leader_target)),
+            ),
+            now,
+        );
+        assert_eq!(
+            commands,
+            vec![(
+                Zone::Follower,
+                Command::SetTemp(TempParam::Celsius(Temperature::from_half_degrees(
+                    leader_target.half_degrees() + 3
+                )))
+            )]
+        );
+    }
+
+    #[test]
+    fn changes_that_never_arrive_stop_being_waited_for() {
+        let now = Instant::now();
+        let mut leader = SimulatedBedJet::default();
+        let mut follower = SimulatedBedJet::default();
+        let mut sync = ZoneSync::new(0);
+        sync.update(Zone::Leader, leader.status(), now);
+        sync.update(Zone::Follower, follower.status(), now);
+        sync.update(
+            Zone::Leader,
+            status(&mut leader, Command::Button(ButtonCode::Dry)),
+            now,
+        );
+
+        // The follower missed the commands, and someone turned it to heat instead
+        let heat = status(&mut follower, Command::Button(ButtonCode::Heat));
+        assert!(sync.update(Zone::Follower, heat, now).is_empty());
+        let later = now + ZoneSync::SETTLE_TIME;
+        let commands = sync.update(Zone::Follower, follower.status(), later);
+        assert_eq!(
+            commands[0],
+            (Zone::Leader, Command::Button(ButtonCode::Heat))
+        );
+    }
+
+    #[test]
+    fn temperatures_for_a_new_mode_wait_for_the_follower_to_reach_it() {
+        let now = Instant::now();
+        let mut leader = SimulatedBedJet::default();
+        let mut follower = SimulatedBedJet::default();
+        follower
+            .apply_command(Command::Button(ButtonCode::Heat))
+            .unwrap();
+        let mut sync = ZoneSync::new(0);
+        sync.update(Zone::Follower, follower.status(), now);
+
+        // Colder than heating allows, so the follower can only take it once it's cooling
+        leader
+            .apply_command(Command::Button(ButtonCode::Cool))
+            .unwrap();
+        let cold = Temperature::from_half_degrees(32);
+        let commands = sync.update(
+            Zone::Leader,
+            status(&mut leader, Command::SetTemp(TempParam::Celsius(cold))),
+            now,
+        );
+        assert_eq!(
+            commands[0],
+            (Zone::Follower, Command::Button(ButtonCode::Cool))
+        );
+        assert!(commands
+            .iter()
+            .all(|(_, command)| !matches!(command, Command::SetTemp(_))));
+
+        let mut commands = commands;
+        while !commands.is_empty() {
+            let (_, command) = commands.remove(0);
+            let reported = status(&mut follower, command);
+            commands.extend(sync.update(Zone::Follower, reported, now));
+        }
+        assert_eq!(follower.status().target_temp, cold);
+    }
+
+    #[tokio::test]
+    async fn keeps_connected_devices_in_sync() {
+        let leader_transport = SimulatorTransport::default();
+        let follower_transport = SimulatorTransport::default();
+        let leader = Arc::new(BedJet::new(leader_transport.clone()));
+        let follower = Arc::new(BedJet::new(follower_transport.clone()));
+
+        let mut tasks = Vec::new();
+        for bedjet in [&leader, &follower] {
+            let bedjet = bedjet.clone();
+            tasks.push(tokio::spawn(async move {
+                let _ = bedjet.handle_notifications().await;
+            }));
+        }
+        for transport in [&leader_transport, &follower_transport] {
+            while !transport.memory().is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+                tokio::task::yield_now().await;
+            }
+        }
+        let (leader_state, leader_state_receiver) = watch::channel(ConnectionState::Connected);
+        let (_follower_state, follower_state_receiver) = watch::channel(ConnectionState::Connected);
+        let sync = tokio::spawn({
+            let (leader, follower) = (leader.clone(), follower.clone());
+            async move {
+                let mut sync = ZoneSync::new(0);
+                let states = (leader_state_receiver, follower_state_receiver);
+                sync_zones(&leader, &follower, states.0, states.1, &mut sync).await
+            }
+        });
+
+        // The devices send their status every so often
+        for transport in [&leader_transport, &follower_transport] {
+            transport.advance(Duration::from_secs(1));
+        }
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        // Colder than standby allows, so the follower only takes it once it's cooling
+        let cold = Temperature::from_half_degrees(32);
+        {
+            let mut device = leader_transport.device();
+            device
+                .apply_command(Command::Button(ButtonCode::Cool))
+                .unwrap();
+            device
+                .apply_command(Command::SetTemp(TempParam::Celsius(cold)))
+                .unwrap();
+        }
+        leader_transport.advance(Duration::ZERO);
+
+        let cooled = tokio::time::timeout(Duration::from_secs(5), async {
+            while follower_transport.device().status().target_temp != cold {
+                tokio::task::yield_now().await;
+            }
+        });
+        cooled
+            .await
+            .expect("the follower never took the temperature");
+        assert_eq!(follower_transport.device().mode(), OperatingMode::Cool);
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        // Nothing was sent back to the leader
+        assert!(leader_transport.memory().writes().is_empty());
+
+        // Disconnecting either device ends the sync
+        leader_state.send_replace(ConnectionState::Disconnected);
+        sync.await.unwrap();
+
+        for task in tasks {
+            task.abort();
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::InterfaceError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use typeshare::typeshare;
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(into = "f32", try_from = "f32")]
+#[typeshare(serialized_as = "number")]
+/// A temperature as the device stores it, in units of 0.5 degrees Celsius.
+///
+/// Serialized as degrees Celsius, so `23.5` is sent to and from the frontend as is.
+pub struct Temperature(u8);
+
+impl Temperature {
+    pub const fn from_half_degrees(half_degrees: u8) -> Self {
+        Self(half_degrees)
+    }
+
+    /// The value the device expects, in units of 0.5 degrees Celsius
+    pub const fn half_degrees(self) -> u8 {
+        self.0
+    }
+
+    /// Rounds to the nearest half degree, failing if the device can't represent it
+    pub fn from_celsius(celsius: f32) -> Result<Self, InterfaceError> {
+        let half_degrees = (celsius * 2.0).round();
+        if !(0.0..=u8::MAX as f32).contains(&half_degrees) {
+            return Err(InterfaceError::InvalidParameter);
+        }
+        Ok(Self(half_degrees as u8))
+    }
+
+    /// Converts to Celsius and rounds to the nearest half degree
+    pub fn from_fahrenheit(fahrenheit: f32) -> Result<Self, InterfaceError> {
+        Self::from_celsius((fahrenheit - 32.0) * 5.0 / 9.0)
+    }
+
+    pub fn celsius(self) -> f32 {
+        self.0 as f32 / 2.0
+    }
+
+    pub fn fahrenheit(self) -> f32 {
+        self.celsius() * 9.0 / 5.0 + 32.0
+    }
+}
+
+impl From<Temperature> for f32 {
+    fn from(value: Temperature) -> Self {
+        value.celsius()
+    }
+}
+
+impl TryFrom<f32> for Temperature {
+    type Error = InterfaceError;
+
+    fn try_from(celsius: f32) -> Result<Self, Self::Error> {
+        Self::from_celsius(celsius)
+    }
+}
+
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}°C", self.celsius())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn celsius_round_trips_in_half_degrees() {
+        for half_degrees in 0..=u8::MAX {
+            let temp = Temperature::from_half_degrees(half_degrees);
+            assert_eq!(Temperature::from_celsius(temp.celsius()).unwrap(), temp);
+        }
+        assert_eq!(Temperature::from_celsius(23.5).unwrap().half_degrees(), 47);
+        assert_eq!(Temperature::from_celsius(23.7).unwrap().half_degrees(), 47);
+    }
+
+    #[test]
+    fn fahrenheit_picks_the_closest_half_degree() {
+        // 75°F is 23.9°C
+        assert_eq!(Temperature::from_fahrenheit(75.0).unwrap().celsius(), 24.0);
+        // 74°F is 23.3°C
+        assert_eq!(Temperature::from_fahrenheit(74.0).unwrap().celsius(), 23.5);
+        assert_eq!(Temperature::from_celsius(25.0).unwrap().fahrenheit(), 77.0);
+    }
+
+    #[test]
+    fn unrepresentable_temperatures_are_rejected() {
+        assert!(Temperature::from_celsius(-1.0).is_err());
+        assert!(Temperature::from_celsius(128.0).is_err());
+        assert!(Temperature::from_celsius(f32::NAN).is_err());
+        assert!(Temperature::from_fahrenheit(20.0).is_err());
+    }
+
+    #[test]
+    fn serializes_as_celsius() {
+        let temp = Temperature::from_half_degrees(47);
+
+        assert_eq!(serde_json::to_string(&temp).unwrap(), "23.5");
+        assert_eq!(serde_json::from_str::<Temperature>("23.5").unwrap(), temp);
+    }
+}
@@ -0,0 +1,441 @@
+use crate::{
+    device::BedJet,
+    proto::{DeviceStatus, OperatingMode},
+    supervisor::{self, ConnectionState},
+    temperature::Temperature,
+    transport::BedJetTransport,
+    Command, FanParam, TempParam,
+};
+use async_trait::async_trait;
+use std::{io, ops::RangeInclusive, path::PathBuf, time::Duration};
+use thiserror::Error;
+use tokio::{fs, sync::watch, time};
+
+#[derive(Error, Debug)]
+pub enum SourceError {
+    #[error("Couldn't read {path}: {source}")]
+    Io { path: String, source: io::Error },
+    #[error("{0:?} isn't a temperature")]
+    Invalid(String),
+    #[error("The sensor hasn't reported a temperature yet")]
+    NoReading,
+    #[error("The sensor has gone away")]
+    Closed,
+}
+
+#[async_trait]
+/// Somewhere to read the temperature under the covers from, since the device only knows the
+/// temperature of the air leaving it
+pub trait TemperatureSource: Send + Sync {
+    /// The latest reading, in degrees Celsius
+    async fn read(&self) -> Result<f32, SourceError>;
+}
+
+#[derive(Debug, Clone)]
+/// A file holding a single temperature in degrees Celsius, kept up to date by something else,
+/// like a script polling a sensor
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl TemperatureSource for FileSource {
+    async fn read(&self) -> Result<f32, SourceError> {
+        let contents = fs::read_to_string(&self.path)
+            .await
+            .map_err(|source| SourceError::Io {
+                path: self.path.display().to_string(),
+                source,
+            })?;
+        let contents = contents.trim();
+        contents
+            .parse()
+            .map_err(|_| SourceError::Invalid(contents.to_owned()))
+    }
+}
+
+#[async_trait]
+/// Readings pushed by a sensor that reports on its own schedule, like an MQTT topic or another
+/// BLE device. `None` until the first reading arrives
+impl TemperatureSource for watch::Receiver<Option<f32>> {
+    async fn read(&self) -> Result<f32, SourceError> {
+        if self.has_changed().is_err() {
+            return Err(SourceError::Closed);
+        }
+        (*self.borrow()).ok_or(SourceError::NoReading)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A PI controller holding the temperature under the covers at a setpoint, by moving the
+/// device's target temperature and fan speed.
+///
+/// Targets are always kept within the limits the device reports for its current mode, and it
+/// only takes over while the device is heating.
+pub struct Thermostat {
+    setpoint: Temperature,
+    /// The accumulated error, in degree seconds
+    integral: f32,
+    failed_readings: u32,
+}
+
+impl Thermostat {
+    /// How often the sensor is read and the device adjusted
+    pub const INTERVAL: Duration = Duration::from_secs(30);
+    /// Degrees the target moves per degree of error
+    const PROPORTIONAL_GAIN: f32 = 2.0;
+    /// Degrees the target moves per degree second of accumulated error
+    const INTEGRAL_GAIN: f32 = 0.002;
+    /// The most the accumulated error can move the target, in degrees
+    const MAX_INTEGRAL_TERM: f32 = 15.0;
+    /// The fan step used when the temperature is right
+    const BASE_FAN: f32 = 9.0;
+    /// Fan steps added per degree below the setpoint
+    const FAN_PER_DEGREE: f32 = 3.0;
+    const FAN_STEPS: RangeInclusive<f32> = 3.0..=19.0;
+    /// Readings outside of this are treated as a faulty sensor
+    const PLAUSIBLE_READINGS: RangeInclusive<f32> = 0.0..=50.0;
+    /// After this many failed readings in a row, the device is put back to the setpoint
+    /// rather than left wherever the controller last had it
+    const MAX_FAILED_READINGS: u32 = 4;
+
+    pub fn new(setpoint: Temperature) -> Self {
+        Self {
+            setpoint,
+            integral: 0.0,
+            failed_readings: 0,
+        }
+    }
+
+    pub fn setpoint(&self) -> Temperature {
+        self.setpoint
+    }
+
+    /// The commands that move the device towards the setpoint, given a reading from the sensor
+    /// (`None` if it couldn't be read), the device's status, and the time since the last update
+    pub fn update(
+        &mut self,
+        reading: Option<f32>,
+        status: &DeviceStatus,
+        elapsed: Duration,
+    ) -> Vec<Command> {
+        if !matches!(
+            status.operating_mode,
+            OperatingMode::NormalHeat | OperatingMode::ExtendedHeat
+        ) {
+            self.integral = 0.0;
+            return Vec::new();
+        }
+
+        let reading = reading.filter(|reading| Self::PLAUSIBLE_READINGS.contains(reading));
+        let Some(reading) = reading else {
+            self.failed_readings += 1;
+            if self.failed_readings < Self::MAX_FAILED_READINGS {
+                return Vec::new();
+            }
+            self.integral = 0.0;
+            return self.commands(status, self.setpoint.celsius(), Self::BASE_FAN);
+        };
+        self.failed_readings = 0;
+
+        let error = self.setpoint.celsius() - reading;
+        let proportional = self.setpoint.celsius() + Self::PROPORTIONAL_GAIN * error;
+        let target = proportional + Self::INTEGRAL_GAIN * self.integral;
+
+        // Only accumulate error while the target can still move that way, so it doesn't wind up
+        // past the device's limits
+        let saturated = (target >= status.max_target_temp.celsius() && error > 0.0)
+            || (target <= status.min_target_temp.celsius() && error < 0.0);
+        if !saturated {
+            let max_integral = Self::MAX_INTEGRAL_TERM / Self::INTEGRAL_GAIN;
+            self.integral =
+                (self.integral + error * elapsed.as_secs_f32()).clamp(-max_integral, max_integral);
+        }
+
+        let target = proportional + Self::INTEGRAL_GAIN * self.integral;
+        let fan = Self::BASE_FAN + Self::FAN_PER_DEGREE * error;
+        self.commands(status, target, fan)
+    }
+
+    /// Commands for whatever differs from the status, after clamping to the device's limits
+    fn commands(&self, status: &DeviceStatus, target: f32, fan: f32) -> Vec<Command> {
+        let target = Temperature::from_celsius(target.max(0.0))
+            .unwrap_or(status.max_target_temp)
+            .clamp(status.min_target_temp, status.max_target_temp);
+        let fan = fan
+            .clamp(*Self::FAN_STEPS.start(), *Self::FAN_STEPS.end())
+            .round() as u8;
+
+        let mut commands = Vec::new();
+        if target != status.target_temp {
+            commands.push(Command::SetTemp(TempParam::Celsius(target)));
+        }
+        if fan != status.fan_step {
+            commands.push(Command::SetFan(FanParam::Step(fan)));
+        }
+        commands
+    }
+}
+
+/// Reads the sensor every [`Thermostat::INTERVAL`] and adjusts the device to match, until the
+/// device is [`gone`](supervisor::gone) going by its connection `state`. Needs
+/// [`BedJet::handle_notifications`] running to see the device's status
+pub async fn run_thermostat<T: BedJetTransport, S: TemperatureSource>(
+    bedjet: &BedJet<T>,
+    mut state: watch::Receiver<ConnectionState>,
+    source: &S,
+    thermostat: &mut Thermostat,
+) {
+    let status = bedjet.subscribe_status();
+    let mut interval = time::interval(Thermostat::INTERVAL);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let mut last_update = None;
+
+    loop {
+        let now = tokio::select! {
+            now = interval.tick() => now,
+            _ = supervisor::gone(&mut state) => return,
+        };
+        if status.has_changed().is_err() {
+            return;
+        }
+        let Some(current) = *status.borrow() else {
+            continue;
+        };
+
+        let elapsed = last_update.map_or(Duration::ZERO, |last| now - last);
+        last_update = Some(now);
+        let reading = source.read().await.ok();
+        for command in thermostat.update(reading, &current, elapsed) {
+            if bedjet.send_command(command).await.is_err() {
+                // Whatever didn't get sent is tried again next time
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        proto::ButtonCode,
+        simulator::{SimulatedBedJet, SimulatorTransport},
+    };
+    use std::sync::Arc;
+
+    /// The air under the covers, warmed by the device's outlet and losing heat to the room
+    struct Bed {
+        celsius: f32,
+    }
+
+    impl Bed {
+        /// Seconds for the outlet to close the gap with the fan at full speed
+        const HEATING_TIME: f32 = 600.0;
+        /// Seconds for the room to close the gap
+        const COOLING_TIME: f32 = 1800.0;
+
+        fn advance(&mut self, status: &DeviceStatus, elapsed: Duration) {
+            let seconds = elapsed.as_secs_f32();
+            let airflow = (status.fan_step as f32 + 1.0) / 20.0;
+            let heating = (status.actual_temp.celsius() - self.celsius) * airflow;
+            let cooling = status.ambient_temp.celsius() - self.celsius;
+            self.celsius += seconds * (heating / Self::HEATING_TIME + cooling / Self::COOLING_TIME);
+        }
+    }
+
+    fn heating_status() -> DeviceStatus {
+        let mut sim = SimulatedBedJet::new(20.0);
+        sim.apply_command(Command::Button(ButtonCode::Heat))
+            .unwrap();
+        sim.status()
+    }
+
+    #[test]
+    fn holds_the_setpoint_against_a_thermal_model() {
+        let mut sim = SimulatedBedJet::new(18.0);
+        sim.apply_command(Command::Button(ButtonCode::ExternalHeat))
+            .unwrap();
+        let mut bed = Bed { celsius: 18.0 };
+        let mut thermostat = Thermostat::new(Temperature::from_celsius(30.0).unwrap());
+
+        for minute in 0..4 * 60 {
+            for _ in 0..2 {
+                let status = sim.status();
+                for command in thermostat.update(Some(bed.celsius), &status, Thermostat::INTERVAL) {
+                    sim.apply_command(command).unwrap();
+                }
+                let status = sim.status();
+                assert!(status.target_temp >= status.min_target_temp);
+                assert!(status.target_temp <= status.max_target_temp);
+
+                sim.advance(Thermostat::INTERVAL);
+                bed.advance(&sim.status(), Thermostat::INTERVAL);
+            }
+
+            if minute >= 2 * 60 {
+                assert!(
+                    (bed.celsius - 30.0).abs() < 1.0,
+                    "{:.2}°C after {minute} minutes",
+                    bed.celsius
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stays_within_the_device_limits() {
+        let status = heating_status();
+        let mut thermostat = Thermostat::new(Temperature::from_celsius(30.0).unwrap());
+
+        assert_eq!(
+            thermostat.update(Some(10.0), &status, Thermostat::INTERVAL),
+            vec![
+                Command::SetTemp(TempParam::Celsius(status.max_target_temp)),
+                Command::SetFan(FanParam::Step(19)),
+            ]
+        );
+        // Saturated, so none of that error built up
+        assert_eq!(thermostat.integral, 0.0);
+
+        assert_eq!(
+            thermostat.update(Some(45.0), &status, Thermostat::INTERVAL),
+            vec![
+                Command::SetTemp(TempParam::Celsius(status.min_target_temp)),
+                Command::SetFan(FanParam::Step(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_setpoint_without_readings() {
+        let status = DeviceStatus {
+            target_temp: Temperature::from_celsius(40.0).unwrap(),
+            fan_step: 19,
+            ..heating_status()
+        };
+        let setpoint = Temperature::from_celsius(30.0).unwrap();
+        let mut thermostat = Thermostat::new(setpoint);
+
+        // A reading no bed could have is as good as none
+        assert!(thermostat
+            .update(Some(-40.0), &status, Thermostat::INTERVAL)
+            .is_empty());
+        for _ in 1..Thermostat::MAX_FAILED_READINGS - 1 {
+            assert!(thermostat
+                .update(None, &status, Thermostat::INTERVAL)
+                .is_empty());
+        }
+        assert_eq!(
+            thermostat.update(None, &status, Thermostat::INTERVAL),
+            vec![
+                Command::SetTemp(TempParam::Celsius(setpoint)),
+                Command::SetFan(FanParam::Step(9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_other_modes_alone() {
+        let mut sim = SimulatedBedJet::new(20.0);
+        let mut thermostat = Thermostat::new(Temperature::from_celsius(30.0).unwrap());
+        assert!(thermostat
+            .update(Some(20.0), &sim.status(), Thermostat::INTERVAL)
+            .is_empty());
+
+        sim.apply_command(Command::Button(ButtonCode::Cool))
+            .unwrap();
+        assert!(thermostat
+            .update(Some(20.0), &sim.status(), Thermostat::INTERVAL)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn reads_temperatures_from_a_file() {
+        let path = std::env::temp_dir().join(format!("bedjet-thermostat-{}", std::process::id()));
+        let source = FileSource::new(&path);
+        assert!(matches!(source.read().await, Err(SourceError::Io { .. })));
+
+        std::fs::write(&path, "27.5\n").unwrap();
+        assert_eq!(source.read().await.unwrap(), 27.5);
+        std::fs::write(&path, "warm").unwrap();
+        assert!(matches!(source.read().await, Err(SourceError::Invalid(_))));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn adjusts_a_connected_device() {
+        let transport = SimulatorTransport::default();
+        transport
+            .device()
+            .apply_command(Command::Button(ButtonCode::Heat))
+            .unwrap();
+        let bedjet = Arc::new(BedJet::new(transport.clone()));
+        let (sensor, source) = watch::channel(None);
+
+        let notifications = tokio::spawn({
+            let bedjet = bedjet.clone();
+            async move {
+                let _ = bedjet.handle_notifications().await;
+            }
+        });
+        while !transport.memory().is_subscribed(BedJet::DEVICE_STATUS_UUID) {
+            tokio::task::yield_now().await;
+        }
+        transport.advance(Duration::from_secs(1));
+        let (state, state_receiver) = watch::channel(ConnectionState::Connected);
+        let thermostat = tokio::spawn({
+            let bedjet = bedjet.clone();
+            async move {
+                let mut thermostat = Thermostat::new(Temperature::from_celsius(30.0).unwrap());
+                run_thermostat(&bedjet, state_receiver, &source, &mut thermostat).await
+            }
+        });
+
+        // Nothing is changed until the sensor reports in
+        time::sleep(Thermostat::INTERVAL * 2).await;
+        assert!(transport.memory().writes().is_empty());
+
+        sensor.send_replace(Some(20.0));
+        time::sleep(Thermostat::INTERVAL).await;
+        let status = transport.device().status();
+        assert_eq!(status.target_temp, status.max_target_temp);
+        assert_eq!(status.fan_step, 19);
+
+        // Reconnecting doesn't stop it, but the supervisor giving up does
+        state.send_replace(ConnectionState::Reconnecting { attempt: 1 });
+        time::sleep(Thermostat::INTERVAL).await;
+        assert!(!thermostat.is_finished());
+        state.send_replace(ConnectionState::Failed);
+        time::timeout(Thermostat::INTERVAL, thermostat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        notifications.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_once_the_supervisor_is_gone() {
+        let bedjet = BedJet::new(SimulatorTransport::default());
+        let (state, state_receiver) = watch::channel(ConnectionState::Connected);
+        let (_sensor, source) = watch::channel(Some(20.0));
+        let mut thermostat = Thermostat::new(Temperature::from_celsius(30.0).unwrap());
+
+        drop(state);
+
+        time::timeout(
+            Thermostat::INTERVAL,
+            run_thermostat(&bedjet, state_receiver, &source, &mut thermostat),
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,329 @@
+use crate::device::DeviceError;
+use async_trait::async_trait;
+use btleplug::{
+    api::{Characteristic, Peripheral as _, ValueNotification, WriteType},
+    platform::Peripheral,
+};
+use futures::{
+    channel::mpsc::{self, UnboundedSender},
+    Stream, StreamExt,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+pub type NotificationStream = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+#[async_trait]
+/// The link between a [`crate::device::BedJet`] and the characteristics it talks to.
+///
+/// Everything is addressed by characteristic UUID so the device layer doesn't need to know
+/// whether it's talking to a real radio or something in memory.
+pub trait BedJetTransport: Debug + Send + Sync {
+    async fn connect(&self) -> Result<(), DeviceError>;
+    async fn disconnect(&self) -> Result<(), DeviceError>;
+    async fn is_connected(&self) -> Result<bool, DeviceError>;
+    async fn read(&self, uuid: Uuid) -> Result<Vec<u8>, DeviceError>;
+    async fn write(&self, uuid: Uuid, data: &[u8]) -> Result<(), DeviceError>;
+    async fn subscribe(&self, uuid: Uuid) -> Result<(), DeviceError>;
+    async fn unsubscribe(&self, uuid: Uuid) -> Result<(), DeviceError>;
+    async fn notifications(&self) -> Result<NotificationStream, DeviceError>;
+}
+
+/// A transport picked at runtime, so the same code can drive a real BedJet or a simulated one
+pub type DynTransport = Arc<dyn BedJetTransport>;
+
+#[async_trait]
+impl<T: BedJetTransport + ?Sized> BedJetTransport for Arc<T> {
+    async fn connect(&self) -> Result<(), DeviceError> {
+        (**self).connect().await
+    }
+
+    async fn disconnect(&self) -> Result<(), DeviceError> {
+        (**self).disconnect().await
+    }
+
+    async fn is_connected(&self) -> Result<bool, DeviceError> {
+        (**self).is_connected().await
+    }
+
+    async fn read(&self, uuid: Uuid) -> Result<Vec<u8>, DeviceError> {
+        (**self).read(uuid).await
+    }
+
+    async fn write(&self, uuid: Uuid, data: &[u8]) -> Result<(), DeviceError> {
+        (**self).write(uuid, data).await
+    }
+
+    async fn subscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        (**self).subscribe(uuid).await
+    }
+
+    async fn unsubscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        (**self).unsubscribe(uuid).await
+    }
+
+    async fn notifications(&self) -> Result<NotificationStream, DeviceError> {
+        (**self).notifications().await
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A transport backed by a btleplug peripheral.
+pub struct PeripheralTransport {
+    peripheral: Peripheral,
+    characteristics: HashMap<Uuid, Characteristic>,
+}
+
+impl PeripheralTransport {
+    /// Captures the characteristics the peripheral currently knows about,
+    /// so services should already have been discovered.
+    pub fn new(peripheral: Peripheral) -> Self {
+        let characteristics = peripheral
+            .characteristics()
+            .into_iter()
+            .map(|c| (c.uuid, c))
+            .collect();
+
+        Self {
+            peripheral,
+            characteristics,
+        }
+    }
+
+    pub fn peripheral(&self) -> &Peripheral {
+        &self.peripheral
+    }
+
+    pub fn has_characteristic(&self, uuid: &Uuid) -> bool {
+        self.characteristics.contains_key(uuid)
+    }
+
+    fn characteristic(&self, uuid: Uuid) -> Result<&Characteristic, DeviceError> {
+        self.characteristics
+            .get(&uuid)
+            .ok_or(DeviceError::MissingCharacteristic(uuid))
+    }
+}
+
+#[async_trait]
+impl BedJetTransport for PeripheralTransport {
+    async fn connect(&self) -> Result<(), DeviceError> {
+        self.peripheral.connect().await?;
+        Ok(self.peripheral.discover_services().await?)
+    }
+
+    async fn disconnect(&self) -> Result<(), DeviceError> {
+        Ok(self.peripheral.disconnect().await?)
+    }
+
+    async fn is_connected(&self) -> Result<bool, DeviceError> {
+        Ok(self.peripheral.is_connected().await?)
+    }
+
+    async fn read(&self, uuid: Uuid) -> Result<Vec<u8>, DeviceError> {
+        Ok(self.peripheral.read(self.characteristic(uuid)?).await?)
+    }
+
+    async fn write(&self, uuid: Uuid, data: &[u8]) -> Result<(), DeviceError> {
+        self.peripheral
+            .write(self.characteristic(uuid)?, data, WriteType::WithoutResponse)
+            .await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        Ok(self
+            .peripheral
+            .subscribe(self.characteristic(uuid)?)
+            .await?)
+    }
+
+    async fn unsubscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        Ok(self
+            .peripheral
+            .unsubscribe(self.characteristic(uuid)?)
+            .await?)
+    }
+
+    async fn notifications(&self) -> Result<NotificationStream, DeviceError> {
+        Ok(self.peripheral.notifications().await?)
+    }
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    values: HashMap<Uuid, Vec<u8>>,
+    writes: Vec<(Uuid, Vec<u8>)>,
+    subscribed: HashSet<Uuid>,
+    listeners: Vec<UnboundedSender<ValueNotification>>,
+    /// Notifications sent before anyone was listening, handed to the first listener
+    pending: Vec<ValueNotification>,
+    disconnected: bool,
+    /// Connecting fails while set
+    unreachable: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+/// An in-memory transport for driving a [`crate::device::BedJet`] without a Bluetooth adapter.
+///
+/// Characteristics only exist once they've been given a value with [`MemoryTransport::set_value`],
+/// and notifications are only delivered for characteristics that have been subscribed to.
+/// It starts out connected, and [`MemoryTransport::drop_connection`] simulates the device going away.
+pub struct MemoryTransport {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a transport with every characteristic a BedJet exposes, each holding an empty value
+    pub fn with_bedjet_characteristics() -> Self {
+        use crate::device::BedJet;
+        let transport = Self::new();
+        for uuid in [
+            BedJet::DEVICE_STATUS_UUID,
+            BedJet::FRIENDLY_NAME_UUID,
+            BedJet::WIFI_SSID_UUID,
+            BedJet::WIFI_PASSWORD_UUID,
+            BedJet::COMMANDS_UUID,
+            BedJet::EXTENDED_DATA_UUID,
+        ] {
+            transport.set_value(uuid, Vec::new());
+        }
+        transport
+    }
+
+    /// Sets the value that will be returned when the characteristic is read
+    pub fn set_value(&self, uuid: Uuid, value: Vec<u8>) {
+        self.state.lock().unwrap().values.insert(uuid, value);
+    }
+
+    pub fn value(&self, uuid: Uuid) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().values.get(&uuid).cloned()
+    }
+
+    /// Pushes a notification to every listener, if the characteristic is subscribed to
+    pub fn notify(&self, uuid: Uuid, value: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        if !state.subscribed.contains(&uuid) {
+            return;
+        }
+
+        let notification = ValueNotification { uuid, value };
+        state
+            .listeners
+            .retain(|listener| listener.unbounded_send(notification.clone()).is_ok());
+
+        if state.listeners.is_empty() {
+            state.pending.push(notification);
+        }
+    }
+
+    /// Acts like the device went out of range. Notification streams end, subscriptions are lost,
+    /// and everything fails until [`BedJetTransport::connect`] is called
+    pub fn drop_connection(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.disconnected = true;
+        state.listeners.clear();
+        state.subscribed.clear();
+        state.pending.clear();
+    }
+
+    /// Makes [`BedJetTransport::connect`] fail, like the device is out of range
+    pub fn set_reachable(&self, reachable: bool) {
+        self.state.lock().unwrap().unreachable = !reachable;
+    }
+
+    pub fn is_subscribed(&self, uuid: Uuid) -> bool {
+        self.state.lock().unwrap().subscribed.contains(&uuid)
+    }
+
+    /// Every write made so far, in order
+    pub fn writes(&self) -> Vec<(Uuid, Vec<u8>)> {
+        self.state.lock().unwrap().writes.clone()
+    }
+
+    /// Returns the writes made so far, and clears them
+    pub fn take_writes(&self) -> Vec<(Uuid, Vec<u8>)> {
+        std::mem::take(&mut self.state.lock().unwrap().writes)
+    }
+
+    fn ensure_exists(state: &MemoryState, uuid: Uuid) -> Result<(), DeviceError> {
+        if state.disconnected {
+            Err(btleplug::Error::NotConnected.into())
+        } else if state.values.contains_key(&uuid) {
+            Ok(())
+        } else {
+            Err(DeviceError::MissingCharacteristic(uuid))
+        }
+    }
+}
+
+#[async_trait]
+impl BedJetTransport for MemoryTransport {
+    async fn connect(&self) -> Result<(), DeviceError> {
+        let mut state = self.state.lock().unwrap();
+        if state.unreachable {
+            return Err(btleplug::Error::DeviceNotFound.into());
+        }
+        state.disconnected = false;
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<(), DeviceError> {
+        self.drop_connection();
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> Result<bool, DeviceError> {
+        Ok(!self.state.lock().unwrap().disconnected)
+    }
+
+    async fn read(&self, uuid: Uuid) -> Result<Vec<u8>, DeviceError> {
+        let state = self.state.lock().unwrap();
+        Self::ensure_exists(&state, uuid)?;
+        Ok(state.values[&uuid].clone())
+    }
+
+    async fn write(&self, uuid: Uuid, data: &[u8]) -> Result<(), DeviceError> {
+        let mut state = self.state.lock().unwrap();
+        Self::ensure_exists(&state, uuid)?;
+        state.writes.push((uuid, data.to_vec()));
+        Ok(())
+    }
+
+    async fn subscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        let mut state = self.state.lock().unwrap();
+        Self::ensure_exists(&state, uuid)?;
+        state.subscribed.insert(uuid);
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, uuid: Uuid) -> Result<(), DeviceError> {
+        let mut state = self.state.lock().unwrap();
+        Self::ensure_exists(&state, uuid)?;
+        state.subscribed.remove(&uuid);
+        Ok(())
+    }
+
+    async fn notifications(&self) -> Result<NotificationStream, DeviceError> {
+        let (send, recv) = mpsc::unbounded();
+        let mut state = self.state.lock().unwrap();
+        if state.disconnected {
+            return Err(btleplug::Error::NotConnected.into());
+        }
+        for notification in state.pending.drain(..) {
+            let _ = send.unbounded_send(notification);
+        }
+        state.listeners.push(send);
+
+        Ok(recv.boxed())
+    }
+}
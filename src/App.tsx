@@ -1,66 +1,49 @@
 import "./App.css";
-import { AppShell, Box, Button, Container, Group, List, Loader, Paper, RingProgress, Select, Slider, Stack } from "@mantine/core";
-import { useBedJetStatus, useBedJetSubscription, useBedJets } from "./hooks";
+import { AppShell, Box, Button, Checkbox, Container, Group, List, Loader, NumberInput, Paper, RingProgress, Select, Slider, Stack, TextInput } from "@mantine/core";
+import { useBedJetStatus, useBedJetSubscription, useBedJets, useForgetDevice, useKnownDevices, useSaveKnownDevice, useSaveSettings, useSetDeviceSetting, useSettings, useStartThermostat, useStopThermostat } from "./hooks";
 import { BluetoothAdapterSelect } from "./components/BluetoothAdapterSelect";
 import { invoke } from "@tauri-apps/api";
-import { ButtonCode, DeviceStatus, OperatingMode } from "./types";
+import { ButtonCode, DeviceSetting, DeviceStatus, KnownDevice, OperatingMode } from "./types";
 import { Command } from "./types";
-
-const upstairs = "99d86151-1751-6ec0-9393-951cae899789"
+import { useSelectedAdapter } from "./AdapterContext";
+import { useState } from "react";
 
 
 
 function App() {
   const devices = useBedJets();
-  const a = useBedJetSubscription();
-
-  const status = useBedJetStatus(upstairs);
+  const knownDevices = useKnownDevices();
+  const settings = useSettings();
+  const saveSettings = useSaveSettings();
+  useBedJetSubscription();
 
-  const data = status.data as DeviceStatus | undefined
+  // Remembered devices are listed even when discovery hasn't found them, since they may be
+  // connected already
+  const ids = [...new Set([...(knownDevices.data ?? []).map((device) => device.id), ...(devices.data ?? [])])]
 
   return (
     <AppShell>
       <Container>
         <Paper>
           <BluetoothAdapterSelect />
+          <Checkbox
+            label="Keep device clocks in sync"
+            checked={settings.data?.sync_clock ?? true}
+            disabled={!settings.data}
+            onChange={(event) => settings.data && saveSettings.mutate({
+              ...settings.data,
+              sync_clock: event.currentTarget.checked
+            })} />
           {
             devices.isLoading
               ? <Loader />
               : <List>
-                {devices.data?.map((device, index) => {
-                  if (device === upstairs) {
-                    return (<Box>
-                      <Group>
-                        <List.Item key={index}>{device}</List.Item>
-                        <Button onClick={() => invoke("connect_bedjet", { bedjetid: device })}  >Connect</Button>
-                        <Button onClick={() => invoke("disconnect_bedjet", { bedjetid: device })}>Disconnect</Button>
-
-                      </Group>
-                      <Container>
-                        {data &&
-                          <Stack spacing={"xl"}>
-
-
-                            <Slider disabled={!data} min={66} max={92}
-                              marks={[...SliderMarks,
-                              { value: (CtoF(data.actual_temp)), label: "Actual" },
-                              ]}
-                              defaultValue={Math.round(CtoF(data.target_temp))}
-                              onChangeEnd={(value) => {
-                                setTemperature(device, data, value)
-                              }} />
-
-
-                            <Slider min={5} max={100} step={5} />
-                          </Stack>
-
-                        }
-                      </Container>
-
-                    </Box>)
-                  }
-
-                })}
+                {ids.map((device) => (
+                  <BedJetItem
+                    key={device}
+                    device={device}
+                    known={knownDevices.data?.find((known) => known.id === device)} />
+                ))}
               </List>
           }
 
@@ -72,6 +55,110 @@ function App() {
 
 }
 
+function BedJetItem({ device, known }: { device: string, known?: KnownDevice }) {
+  const status = useBedJetStatus(device);
+  const { selectedAdapter } = useSelectedAdapter();
+  const saveDevice = useSaveKnownDevice();
+  const forgetDevice = useForgetDevice();
+
+  const data = status.data as DeviceStatus | undefined
+
+  return (<Box>
+    <Group>
+      <List.Item>{known ? `${known.nickname} (${device})` : device}</List.Item>
+      <Button onClick={() => invoke("connect_bedjet", { bedjetid: device })}  >Connect</Button>
+      <Button onClick={() => invoke("disconnect_bedjet", { bedjetid: device })}>Disconnect</Button>
+      {known
+        ? <>
+          <Checkbox
+            label="Connect on startup"
+            checked={known.auto_connect}
+            onChange={(event) => saveDevice.mutate({ device: { ...known, auto_connect: event.currentTarget.checked }, isNew: false })} />
+          <Button variant="subtle" onClick={() => forgetDevice.mutate(device)}>Forget</Button>
+        </>
+        : <Button variant="subtle" onClick={() => saveDevice.mutate({
+          device: { id: device, nickname: device, preferred_adapter: selectedAdapter, auto_connect: false },
+          isNew: true
+        })}>Remember</Button>
+      }
+    </Group>
+    <Container>
+      {data &&
+        <Stack spacing={"xl"}>
+
+
+          <Slider disabled={!data} min={66} max={92}
+            marks={[...SliderMarks,
+            { value: (CtoF(data.actual_temp)), label: "Actual" },
+            ]}
+            defaultValue={Math.round(CtoF(data.target_temp))}
+            onChangeEnd={(value) => {
+              setTemperature(device, data, value)
+            }} />
+
+
+          <Slider min={5} max={100} step={5} />
+
+          <ThermostatControl device={device} />
+
+          <SettingsControl device={device} />
+        </Stack>
+
+      }
+    </Container>
+
+  </Box>)
+}
+
+// Holds the temperature read from a sensor file at a setpoint, while the device is heating
+function ThermostatControl({ device }: { device: string }) {
+  const [path, setPath] = useState("");
+  const [setpoint, setSetpoint] = useState<number | "">(30);
+  const startThermostat = useStartThermostat();
+  const stopThermostat = useStopThermostat();
+
+  return (<Group>
+    <TextInput
+      label="Sensor file"
+      value={path}
+      onChange={(event) => setPath(event.currentTarget.value)} />
+    <NumberInput
+      label="Setpoint (°C)"
+      precision={1}
+      step={0.5}
+      value={setpoint}
+      onChange={setSetpoint} />
+    <Button
+      disabled={!path || setpoint === ""}
+      loading={startThermostat.isLoading}
+      onClick={() => setpoint !== "" && startThermostat.mutate({ bedjetid: device, setpoint, path })}>
+      Start thermostat
+    </Button>
+    <Button variant="subtle" onClick={() => stopThermostat.mutate(device)}>Stop thermostat</Button>
+  </Group>)
+}
+
+const SettingLabels: Record<DeviceSetting, string> = {
+  [DeviceSetting.LowPower]: "Low power",
+  [DeviceSetting.BeeperMuted]: "Mute beeper",
+  [DeviceSetting.RingOfLight]: "Ring of light",
+};
+
+// The device doesn't report these back, so there's no state to show, only buttons to set them
+function SettingsControl({ device }: { device: string }) {
+  const setDeviceSetting = useSetDeviceSetting();
+
+  return (<Stack spacing={"xs"}>
+    {Object.values(DeviceSetting).map((setting) => (
+      <Group key={setting}>
+        {SettingLabels[setting]}
+        <Button variant="light" onClick={() => setDeviceSetting.mutate({ bedjetid: device, setting, enabled: true })}>On</Button>
+        <Button variant="light" onClick={() => setDeviceSetting.mutate({ bedjetid: device, setting, enabled: false })}>Off</Button>
+      </Group>
+    ))}
+  </Stack>)
+}
+
 async function send_command(bedjetid: string, command: Command) {
   await invoke("send_command", { bedjetid, command })
 }
@@ -119,13 +206,13 @@ export async function setTemperature(id: string, deviceStatus: DeviceStatus, tar
 
   if (deviceStatus.operating_mode !== requiredMode) {
     // save the current timer and fan step
-    const originalTimer = deviceStatus.remaining_duration;
+    const { remaining_hours: hours, remaining_minutes: minutes } = deviceStatus;
     const originalFanStep = deviceStatus.fan_step;
 
     // switch the mode
     await send_command(id, { type: "Button", content: requiredButton });
     // restore the timer and fan step
-    await send_command(id, { type: "SetTime", content: { hours: Math.floor(originalTimer.secs / 3600), minutes: Math.floor((originalTimer.secs % 3600) / 60) } });
+    await send_command(id, { type: "SetTime", content: { hours, minutes } });
     await send_command(id, { type: "SetFan", content: { type: "Percent", value: originalFanStep } });
   }
 
@@ -1,11 +1,36 @@
-import { useQuery, useQueryClient } from "@tanstack/react-query";
+import { useMutation, useQuery, useQueryClient } from "@tanstack/react-query";
 import { invoke } from "@tauri-apps/api";
 import { useSelectedAdapter } from "../AdapterContext";
 import { UnlistenFn, listen } from "@tauri-apps/api/event"
 import { useEffect, useState } from "react";
-import { DeviceStatus, DeviceStatusEvent } from "../types";
+import { AdapterEvent, DeviceSetting, DeviceStatus, DeviceStatusEvent, DiscoveryEvent, KnownDevice, Settings, Temperature } from "../types";
 
 export function useAdapters() {
+  const queryClient = useQueryClient();
+
+  useEffect(() => {
+    const unlisten = listen<AdapterEvent>("Adapter", (event) => {
+      queryClient.setQueryData<string[]>(["adapters"], (adapters = []) => {
+        switch (event.payload.type) {
+          case "AdapterAdded": {
+            const { name } = event.payload.content
+            return adapters.includes(name) ? adapters : [...adapters, name]
+          }
+          case "AdapterRemoved": {
+            const { name } = event.payload.content
+            return adapters.filter((adapter) => adapter !== name)
+          }
+          case "AdapterStateChanged":
+            return adapters
+        }
+      })
+    });
+
+    return () => {
+      unlisten.then((unlisten) => unlisten());
+    }
+  }, [queryClient]);
+
   return useQuery({
     queryKey: ["adapters"],
     queryFn: () => invoke<string[]>("get_btle_adapters"),
@@ -13,11 +38,111 @@ export function useAdapters() {
   })
 }
 
+export function useKnownDevices() {
+  return useQuery({
+    queryKey: ["known-devices"],
+    queryFn: () => invoke<KnownDevice[]>("get_known_devices"),
+    staleTime: Infinity
+  })
+}
+
+export function useSaveKnownDevice() {
+  const queryClient = useQueryClient();
+  return useMutation({
+    mutationFn: ({ device, isNew }: { device: KnownDevice, isNew: boolean }) =>
+      invoke(isNew ? "add_known_device" : "update_known_device", { device }),
+    onSuccess: () => queryClient.invalidateQueries({ queryKey: ["known-devices"] })
+  })
+}
+
+export function useForgetDevice() {
+  const queryClient = useQueryClient();
+  return useMutation({
+    mutationFn: (bedjetid: string) => invoke("forget_device", { bedjetid }),
+    onSuccess: () => queryClient.invalidateQueries({ queryKey: ["known-devices"] })
+  })
+}
+
+export function useSettings() {
+  return useQuery({
+    queryKey: ["settings"],
+    queryFn: () => invoke<Settings>("get_settings"),
+    staleTime: Infinity
+  })
+}
+
+export function useSaveSettings() {
+  const queryClient = useQueryClient();
+  return useMutation({
+    mutationFn: (settings: Settings) => invoke("set_settings", { settings }),
+    onSuccess: () => queryClient.invalidateQueries({ queryKey: ["settings"] })
+  })
+}
+
+export function useStartThermostat() {
+  return useMutation({
+    mutationFn: ({ bedjetid, setpoint, path }: { bedjetid: string, setpoint: Temperature, path: string }) =>
+      invoke("start_thermostat", { bedjetid, setpoint, path })
+  })
+}
+
+export function useStopThermostat() {
+  return useMutation({
+    mutationFn: (bedjetid: string) => invoke("stop_thermostat", { bedjetid })
+  })
+}
+
+export function useSetDeviceSetting() {
+  return useMutation({
+    mutationFn: ({ bedjetid, setting, enabled }: { bedjetid: string, setting: DeviceSetting, enabled: boolean }) =>
+      invoke("set_device_setting", { bedjetid, setting, enabled })
+  })
+}
+
 export function useBedJets() {
+  const queryClient = useQueryClient();
   const { selectedAdapter } = useSelectedAdapter()
+
+  useEffect(() => {
+    if (!selectedAdapter) return;
+    // Cleanup can run before listen resolves, in which case the listener is dropped as soon as it does
+    let cancelled = false;
+    let unlisten: UnlistenFn | undefined;
+
+    async function discover() {
+      const stopListening = await listen<DiscoveryEvent>("Discovery", (event) => {
+        queryClient.setQueryData<string[]>([selectedAdapter, "bedjets"], (devices = []) => {
+          switch (event.payload.type) {
+            case "DeviceDiscovered":
+            case "DeviceUpdated": {
+              const { id } = event.payload.content
+              return devices.includes(id) ? devices : [...devices, id]
+            }
+            case "DeviceLost":
+              return devices.filter((id) => id !== event.payload.content)
+          }
+        })
+      });
+      if (cancelled) {
+        stopListening();
+        return;
+      }
+      unlisten = stopListening;
+      await invoke("start_discovery", { adapter: selectedAdapter });
+    }
+    discover();
+
+    return () => {
+      cancelled = true;
+      if (unlisten) unlisten();
+      invoke("stop_discovery");
+    }
+  }, [selectedAdapter, queryClient]);
+
   return useQuery({
     queryKey: [selectedAdapter, "bedjets"],
-    queryFn: () => invoke<string[]>("scan_bedjets", { adapter: selectedAdapter }),
+    queryFn: async () => [] as string[],
+    staleTime: Infinity,
     enabled: !!selectedAdapter
   })
 }
@@ -31,19 +156,26 @@ export function useBedJetSubscription() {
 
 
   useEffect(() => {
+    let cancelled = false;
     let unlisten: UnlistenFn | undefined;
 
     async function fetchAndListen() {
 
-      unlisten = await listen<DeviceStatusEvent>('DeviceStatus', (event) => {
+      const stopListening = await listen<DeviceStatusEvent>('DeviceStatus', (event) => {
         queryClient.setQueryData<any>([selectedAdapter, "bedjets", event.payload.id, "status"], event.payload.status)
       });
+      if (cancelled) {
+        stopListening();
+        return;
+      }
+      unlisten = stopListening;
       setIsListening(true);
 
     }
     fetchAndListen();
 
     return () => {
+      cancelled = true;
       if (unlisten) unlisten();
       setIsListening(false)
     }
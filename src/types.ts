@@ -3,6 +3,13 @@ export type Duration = {
 	secs: number
 }
 
+/**
+ * A temperature as the device stores it, in units of 0.5 degrees Celsius.
+ * 
+ * Serialized as degrees Celsius, so `23.5` is sent to and from the frontend as is.
+ */
+export type Temperature = number;
+
 export enum OperatingMode {
 	Standby = "Standby",
 	NormalHeat = "NormalHeat",
@@ -45,22 +52,34 @@ export enum UpdateStatus {
 	UpdateFailed = "UpdateFailed",
 }
 
+export enum TemperatureUnit {
+	Celsius = "Celsius",
+	Fahrenheit = "Fahrenheit",
+}
+
 export interface DeviceStatus {
 	/** The total runtime left on the device */
-	remaining_duration: Duration;
-	/** Temp in degrees C */
-	actual_temp: number;
-	/** Temp in degrees C */
-	target_temp: number;
+	remaining_hours: number;
+	remaining_minutes: number;
+	remaining_seconds: number;
+	actual_temp: Temperature;
+	target_temp: Temperature;
 	operating_mode: OperatingMode;
+	/** Represented as a number between 0-19 */
 	fan_step: number;
 	/** Maximum runtime for the current mode */
-	max_duration: Duration;
-	min_target_temp: number;
-	max_target_temp: number;
-	ambient_temp: number;
+	max_duration_hours: number;
+	max_duration_minutes: number;
+	min_target_temp: Temperature;
+	max_target_temp: Temperature;
+	ambient_temp: Temperature;
 	shutdown_code: ShutDownCode;
-	current_update_state: UpdateStatus;
+	update_status: UpdateStatus;
+	/**
+	 * Bytes 14, 15, 18 through 24 and 26 of the packet, which haven't been confirmed against a
+	 * real device. Kept so the packet can be written back out unchanged
+	 */
+	unknown: number[];
 }
 
 export interface DeviceStatusEvent {
@@ -68,6 +87,156 @@ export interface DeviceStatusEvent {
 	status: DeviceStatus;
 }
 
+/**
+ * A device setting that's turned on and off with a pair of buttons.
+ * 
+ * The status packet doesn't report these in any byte that's been confirmed against a real
+ * device, so they're only ever set, not read back
+ */
+export enum DeviceSetting {
+	LowPower = "LowPower",
+	BeeperMuted = "BeeperMuted",
+	RingOfLight = "RingOfLight",
+}
+
+/** What `BedJet::send_command` does with a temperature outside the range the current mode allows */
+export enum TemperaturePolicy {
+	/** Fail with `DeviceError::OutOfRange` */
+	Reject = "Reject",
+	/** Send the closest temperature that is allowed instead */
+	Clamp = "Clamp",
+}
+
+/** Where a supervised BedJet's connection is at */
+export type ConnectionState = 
+	/** Making the first connection */
+	| { type: "Connecting", content?: undefined }
+	/** Connected and receiving status notifications */
+	| { type: "Connected", content?: undefined }
+	/** The connection was lost, and this is the attempt being made to get it back */
+	| { type: "Reconnecting", content: {
+	attempt: number;
+}}
+	/** Every reconnection attempt failed, so the supervisor gave up */
+	| { type: "Failed", content?: undefined }
+	/** The connection was closed on purpose, or the adapter it was on went away */
+	| { type: "Disconnected", content?: undefined };
+
+/** A connection state change for a specific device, as sent to the frontend */
+export interface ConnectionStateEvent {
+	id: string;
+	state: ConnectionState;
+}
+
+/**
+ * The errors returned to the frontend from Tauri commands.
+ * 
+ * Underlying errors are carried as their messages, since they can't be serialized themselves.
+ */
+export type AppError = 
+	| { type: "AdapterNotFound", content?: undefined }
+	| { type: "AdapterUnavailable", content: string }
+	| { type: "DeviceNotFound", content: string }
+	| { type: "GroupNotFound", content: string }
+	| { type: "NotConnected", content?: undefined }
+	| { type: "MissingCharacteristic", content: string }
+	| { type: "Protocol", content: string }
+	| { type: "Bluetooth", content: string }
+	| { type: "Timeout", content?: undefined }
+	/** The known devices couldn't be changed */
+	| { type: "Registry", content: string }
+	| { type: "InvalidSchedule", content: string }
+	/** Saved settings couldn't be read or written */
+	| { type: "Storage", content: string }
+	/** The device is reachable, but refused or failed what was asked of it */
+	| { type: "Device", content: string }
+	/** A temperature sensor couldn't be read */
+	| { type: "Sensor", content: string };
+
+/** How a command sent to a group went for one of its devices */
+export interface DeviceCommandResult {
+	id: string;
+	/** Why the command failed, or `None` if it was sent */
+	error?: AppError;
+}
+
+/** One side of a dual-zone bed */
+export enum Zone {
+	/** Whose target temperature the follower's offset is measured from */
+	Leader = "Leader",
+	Follower = "Follower",
+}
+
+/** A BedJet seen advertising during discovery */
+export interface DiscoveredDevice {
+	id: string;
+	/** The advertised name, if the device has sent one yet */
+	name?: string;
+	/** Signal strength in dBm */
+	rssi?: number;
+	/** When the device was last heard from, in milliseconds since the Unix epoch */
+	last_seen: number;
+}
+
+/** Changes to the list of discovered devices, as sent to the frontend */
+export type DiscoveryEvent = 
+	| { type: "DeviceDiscovered", content: DiscoveredDevice }
+	| { type: "DeviceUpdated", content: DiscoveredDevice }
+	/** The device with this id hasn't been heard from in a while */
+	| { type: "DeviceLost", content: string };
+
+/** A BedJet the user has chosen to remember */
+export interface KnownDevice {
+	/** The peripheral id, which is the address on platforms that expose it */
+	id: string;
+	nickname: string;
+	room?: string;
+	/** The adapter to connect through, if it shouldn't be whichever is selected */
+	preferred_adapter?: string;
+	/** The units to show this device's temperatures in */
+	preferred_units?: TemperatureUnit;
+	/** Connect in the background when the app starts */
+	auto_connect: boolean;
+}
+
+/** App-wide preferences */
+export interface Settings {
+	/** Set each device's clock to local time when it connects, and keep it there */
+	sync_clock: boolean;
+}
+
+/** Whether an adapter can be used */
+export enum AdapterState {
+	Available = "Available",
+	/**
+	 * Still plugged in, but not answering requests, usually because it's powered off.
+	 * btleplug doesn't report power state directly, so this is how it shows up
+	 */
+	Unavailable = "Unavailable",
+}
+
+/** Changes to the Bluetooth adapters on the system, as sent to the frontend */
+export type AdapterEvent = 
+	| { type: "AdapterAdded", content: {
+	name: string;
+	state: AdapterState;
+}}
+	| { type: "AdapterRemoved", content: {
+	name: string;
+}}
+	| { type: "AdapterStateChanged", content: {
+	name: string;
+	state: AdapterState;
+}};
+
+/** What to do when the adapter asked for can't be used */
+export enum AdapterFallback {
+	/** Fail, so the user can pick another adapter */
+	Strict = "Strict",
+	/** Use the first available adapter instead */
+	AnyAvailable = "AnyAvailable",
+}
+
 export type Command =
 	| { type: "Button", content: ButtonCode }
 	| {
@@ -83,10 +252,14 @@ export type Command =
 			hours: number;
 			minutes: number;
 		}
-	};
+	}
+	/** Asks the device to put the value of a parameter in the extended data characteristic */
+	| { type: "GetParam", content: ParameterCode };
 
 export type Temp =
-	| { type: "Celsius", value: number }
+	/** The temperature in degrees Celsius, with half degree precision */
+	| { type: "Celsius", value: Temperature }
+	/** The temperature in whole degrees Fahrenheit */
 	| { type: "Fahrenheit", value: number };
 
 export type Fan =
@@ -156,5 +329,109 @@ export enum CommandClass {
 	SetFan = "SetFan",
 	SetClock = "SetClock",
 	SetParameter = "SetParameter",
+	GetParameter = "GetParameter",
+}
+
+export interface Version {
+	major: number;
+	minor: number;
+	patch: number;
+}
+
+/** The firmware running on the device, as reported by `ParameterCode::FirmwareVersionCodes` */
+export interface FirmwareInfo {
+	main_board: Version;
+	/** Only present on devices with a Wi-Fi module */
+	wifi_module?: Version;
+}
+
+/** How a firmware update is going */
+export type FirmwareUpdateEvent = 
+	/** The device reported a new status */
+	| { type: "Progress", content: UpdateStatus }
+	/** The device restarted into the new firmware, and is being reconnected to */
+	| { type: "Reconnecting", content?: undefined };
+
+/** How a firmware update ended, when it didn't fail */
+export type FirmwareUpdateOutcome = 
+	/** The device already had the latest firmware */
+	| { type: "UpToDate", content?: undefined }
+	/** The device restarted with this firmware */
+	| { type: "Updated", content: FirmwareInfo };
+
+/** A firmware update event for a specific device, as sent to the frontend */
+export interface FirmwareUpdateProgressEvent {
+	id: string;
+	event: FirmwareUpdateEvent;
+}
+
+/** A single step of a biorhythm program */
+export interface BiorhythmStep {
+	mode: OperatingMode;
+	temperature: Temperature;
+	/** Represented as a number between 0-19 */
+	fan_step: number;
+	/** How long the step runs for */
+	hours: number;
+	minutes: number;
+}
+
+/** A named, ordered list of steps stored in one of the device's three biorhythm slots */
+export interface BiorhythmProgram {
+	/** Cannot contain a String longer than 15 bytes. */
+	name: string;
+	steps: BiorhythmStep[];
+}
+
+/** When a step of a schedule starts */
+export type StepStart = 
+	/** The next time the clock reaches this time of day, in local time */
+	| { type: "At", content: {
+	hours: number;
+	minutes: number;
+}}
+	/** This long after the previous step starts, or the schedule if it's the first step */
+	| { type: "After", content: {
+	minutes: number;
+}};
+
+/** A change a schedule makes to a device */
+export interface ScheduleStep {
+	start: StepStart;
+	mode: OperatingMode;
+	/** Left as the mode sets it if `None` */
+	temperature?: Temp;
+	/** Left as the mode sets it if `None` */
+	fan?: Fan;
+}
+
+/**
+ * Steps run on the host one after another, for programs the device's biorhythm slots can't
+ * hold
+ */
+export interface Schedule {
+	name: string;
+	steps: ScheduleStep[];
+}
+
+/** A schedule running on a device */
+export interface ScheduleRun {
+	device_id: string;
+	schedule: Schedule;
+	/** When the schedule was started, in local time */
+	started: string;
+	/** The last step sent to the device, so it isn't sent again when the schedule resumes */
+	applied_step?: number;
+}
+
+/** Where a running schedule is at, as sent to the frontend */
+export interface ScheduleProgressEvent {
+	device_id: string;
+	name: string;
+	/** The step the device was last put into */
+	step?: number;
+	next_step_at?: string;
+	/** Why the last step couldn't be applied */
+	error?: string;
 }
 